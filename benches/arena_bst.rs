@@ -0,0 +1,134 @@
+//! Compare `ArenaBst` against the boxed `BinarySearchTree`.
+//!
+//! Run with `cargo bench --bench arena_bst`. Each operation is timed over
+//! the same random values, and the best of a few rounds is reported.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use algorithms_in_rust::{
+    arena_bst::ArenaBst, binary_search_tree::BinarySearchTree,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const LEN: usize = 200_000;
+const ROUNDS: usize = 5;
+
+/// Run `f` `ROUNDS` times on a fresh input from `setup`, returning the
+/// shortest time.
+fn best<I, O>(
+    mut setup: impl FnMut() -> I,
+    mut f: impl FnMut(I) -> O,
+) -> Duration {
+    (0..ROUNDS)
+        .map(|_| {
+            let input = setup();
+            let start = Instant::now();
+
+            let output = black_box(f(input));
+            let elapsed = start.elapsed();
+
+            // dropping the output is timed separately
+            drop(output);
+
+            elapsed
+        })
+        .min()
+        .unwrap()
+}
+
+fn report(operation: &str, boxed: Duration, arena: Duration) {
+    println!(
+        "{operation:<8} boxed {:>10.2?}  arena {:>10.2?}  ({:.2}x)",
+        boxed,
+        arena,
+        boxed.as_secs_f64() / arena.as_secs_f64()
+    );
+}
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let values: Vec<u64> = (0..LEN).map(|_| rng.gen()).collect();
+    let probes: Vec<u64> = (0..LEN)
+        .map(|i| if i % 2 == 0 { values[i] } else { rng.gen() })
+        .collect();
+
+    // both trees take the values one by one in the same order, so they
+    // have the same shape; collecting into a `BinarySearchTree` would
+    // balance it instead
+    let boxed = BinarySearchTree::from(values.clone());
+    let arena: ArenaBst<u64> = values.iter().copied().collect();
+
+    println!("{LEN} random u64 values, best of {ROUNDS} rounds");
+
+    report(
+        "insert",
+        best(BinarySearchTree::new, |mut tree| {
+            for &v in &values {
+                tree.insert(v);
+            }
+
+            tree
+        }),
+        best(ArenaBst::new, |mut tree| {
+            for &v in &values {
+                tree.insert(v);
+            }
+
+            tree
+        }),
+    );
+    report(
+        "search",
+        best(
+            || (),
+            |_| {
+                probes
+                    .iter()
+                    .filter(|v| boxed.search(v))
+                    .count()
+            },
+        ),
+        best(
+            || (),
+            |_| {
+                probes
+                    .iter()
+                    .filter(|v| arena.search(v))
+                    .count()
+            },
+        ),
+    );
+    report(
+        "iterate",
+        // the values are random, so the sums wrap around
+        best(
+            || (),
+            |_| {
+                boxed
+                    .iter()
+                    .fold(0u64, |a, v| a.wrapping_add(*v))
+            },
+        ),
+        best(
+            || (),
+            |_| {
+                arena
+                    .iter()
+                    .fold(0u64, |a, v| a.wrapping_add(*v))
+            },
+        ),
+    );
+    report(
+        "clone",
+        best(|| (), |_| boxed.clone()),
+        best(|| (), |_| arena.clone()),
+    );
+    report(
+        "drop",
+        best(|| boxed.clone(), drop),
+        best(|| arena.clone(), drop),
+    );
+}
@@ -0,0 +1,448 @@
+use std::{cmp::Ordering, mem};
+
+/// An unbalanced binary search tree that keeps its nodes in a single `Vec`
+/// and links them by `u32` index.
+///
+/// It behaves like a
+/// [`BinarySearchTree`](crate::binary_search_tree::BinarySearchTree) with
+/// the default policy, keeping duplicates in nodes of their own. Nodes that
+/// are removed leave a vacant slot behind, which is put on a free list and
+/// reused by the next insertion. Since no node owns another, cloning and
+/// dropping the tree are plain operations on the `Vec`, however deep the
+/// tree is.
+#[derive(Debug, Clone)]
+pub struct ArenaBst<T> {
+    slots: Vec<Slot<T>>,
+    root: u32,
+    // the first vacant slot, each of which links to the next one
+    free: u32,
+    len: usize,
+}
+
+/// The index standing for a missing node.
+const NIL: u32 = u32::MAX;
+
+#[derive(Debug, Clone)]
+enum Slot<T> {
+    Occupied(Node<T>),
+    Vacant { next: u32 },
+}
+
+#[derive(Debug, Clone)]
+struct Node<T> {
+    value: T,
+    left: u32,
+    right: u32,
+}
+
+impl<T> Node<T> {
+    fn child(&self, side: Ordering) -> u32 {
+        match side {
+            Ordering::Less => self.left,
+            _ => self.right,
+        }
+    }
+
+    fn child_mut(&mut self, side: Ordering) -> &mut u32 {
+        match side {
+            Ordering::Less => &mut self.left,
+            _ => &mut self.right,
+        }
+    }
+}
+
+impl<T> Default for ArenaBst<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ArenaBst<T> {
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Create an empty tree with room for `capacity` nodes.
+    pub fn with_capacity(capacity: usize) -> Self {
+        ArenaBst {
+            slots: Vec::with_capacity(capacity),
+            root: NIL,
+            free: NIL,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Remove every value, keeping the allocated slots.
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.root = NIL;
+        self.free = NIL;
+        self.len = 0;
+    }
+
+    pub fn minimum(&self) -> Option<&T> {
+        self.node(self.edge(self.root, Ordering::Less)?)
+            .map(|node| &node.value)
+    }
+
+    pub fn maximum(&self) -> Option<&T> {
+        self.node(self.edge(self.root, Ordering::Greater)?)
+            .map(|node| &node.value)
+    }
+
+    /// Iterate over the values of the tree in ascending order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        let mut iter = Iter {
+            tree: self,
+            stack: Vec::new(),
+            len: self.len,
+        };
+
+        iter.push_left_spine(self.root);
+
+        iter
+    }
+
+    fn node(&self, index: u32) -> Option<&Node<T>> {
+        match self.slots.get(index as usize)? {
+            Slot::Occupied(node) => Some(node),
+            Slot::Vacant { .. } => None,
+        }
+    }
+
+    fn node_mut(&mut self, index: u32) -> &mut Node<T> {
+        match &mut self.slots[index as usize] {
+            Slot::Occupied(node) => node,
+            Slot::Vacant { .. } => unreachable!("links only lead to nodes"),
+        }
+    }
+
+    /// Return the index of the last node on the path from `index` that
+    /// always takes the `side` child, or `None` if `index` is `NIL`.
+    fn edge(&self, mut index: u32, side: Ordering) -> Option<u32> {
+        let mut node = self.node(index)?;
+
+        while let Some(child) = self.node(node.child(side)) {
+            index = node.child(side);
+            node = child;
+        }
+
+        Some(index)
+    }
+
+    /// Store `node` in a vacant slot, or a new one if there is none, and
+    /// return its index.
+    fn allocate(&mut self, node: Node<T>) -> u32 {
+        if self.free == NIL {
+            let index = u32::try_from(self.slots.len())
+                .ok()
+                .filter(|&index| index != NIL)
+                .expect("an ArenaBst holds fewer than u32::MAX nodes");
+
+            self.slots.push(Slot::Occupied(node));
+
+            return index;
+        }
+
+        let index = self.free;
+        let slot = &mut self.slots[index as usize];
+
+        let Slot::Vacant { next } = *slot else {
+            unreachable!("the free list only holds vacant slots");
+        };
+
+        *slot = Slot::Occupied(node);
+        self.free = next;
+
+        index
+    }
+
+    /// Put the slot at `index` on the free list and return its node.
+    fn release(&mut self, index: u32) -> Node<T> {
+        let vacant = Slot::Vacant { next: self.free };
+
+        self.free = index;
+
+        match mem::replace(&mut self.slots[index as usize], vacant) {
+            Slot::Occupied(node) => node,
+            Slot::Vacant { .. } => unreachable!("links only lead to nodes"),
+        }
+    }
+
+    /// Point the link at `parent` on the `side` it was followed from, or
+    /// the root if there is no parent, to `child`.
+    fn relink(&mut self, parent: Option<(u32, Ordering)>, child: u32) {
+        match parent {
+            Some((parent, side)) => {
+                *self.node_mut(parent).child_mut(side) = child
+            },
+            None => self.root = child,
+        }
+    }
+}
+
+impl<T> ArenaBst<T>
+where
+    T: Ord,
+{
+    pub fn insert(&mut self, value: T) {
+        let mut parent = None;
+        let mut index = self.root;
+
+        while let Some(node) = self.node(index) {
+            let side = match value.cmp(&node.value) {
+                Ordering::Less => Ordering::Less,
+                _ => Ordering::Greater,
+            };
+
+            parent = Some((index, side));
+            index = node.child(side);
+        }
+
+        let index = self.allocate(Node {
+            value,
+            left: NIL,
+            right: NIL,
+        });
+
+        self.relink(parent, index);
+        self.len += 1;
+    }
+
+    pub fn search(&self, value: &T) -> bool {
+        let mut node = self.node(self.root);
+
+        while let Some(n) = node {
+            node = match value.cmp(&n.value) {
+                Ordering::Equal => return true,
+                side => self.node(n.child(side)),
+            };
+        }
+
+        false
+    }
+
+    /// Remove one occurrence of `value` from the tree and return it, or
+    /// `None` if the tree does not contain it.
+    pub fn remove(&mut self, value: &T) -> Option<T> {
+        let mut parent = None;
+        let mut index = self.root;
+
+        loop {
+            let node = self.node(index)?;
+
+            match value.cmp(&node.value) {
+                Ordering::Equal => break,
+                side => {
+                    parent = Some((index, side));
+                    index = node.child(side);
+                },
+            }
+        }
+
+        let node = self.node(index).unwrap();
+        let (left, right) = (node.left, node.right);
+
+        if left == NIL || right == NIL {
+            let child = if left == NIL { right } else { left };
+
+            self.relink(parent, child);
+            self.len -= 1;
+
+            return Some(self.release(index).value);
+        }
+
+        // move the least value of the right subtree into the node, and
+        // release the node it came from instead
+        let mut successor_parent = (index, Ordering::Greater);
+        let mut successor = right;
+
+        while let Some(left) = self
+            .node(successor)
+            .map(|node| node.left)
+            .filter(|&left| left != NIL)
+        {
+            successor_parent = (successor, Ordering::Less);
+            successor = left;
+        }
+
+        let successor = self.release(successor);
+
+        self.relink(Some(successor_parent), successor.right);
+        self.len -= 1;
+
+        Some(mem::replace(
+            &mut self.node_mut(index).value,
+            successor.value,
+        ))
+    }
+}
+
+impl<T> FromIterator<T> for ArenaBst<T>
+where
+    T: Ord,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut tree = Self::with_capacity(iter.size_hint().0);
+
+        for value in iter {
+            tree.insert(value);
+        }
+
+        tree
+    }
+}
+
+pub struct Iter<'a, T> {
+    tree: &'a ArenaBst<T>,
+    stack: Vec<&'a Node<T>>,
+    len: usize,
+}
+
+impl<'a, T> Iter<'a, T> {
+    fn push_left_spine(&mut self, mut index: u32) {
+        while let Some(node) = self.tree.node(index) {
+            self.stack.push(node);
+            index = node.left;
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+
+        self.push_left_spine(node.right);
+        self.len -= 1;
+
+        Some(&node.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_data() -> (ArenaBst<usize>, Vec<usize>, Vec<usize>) {
+        let vs = vec![5, 1, 4, 4, 4, 6, 5, 4, 5, 6, 5, 9, 7, 6];
+        let mut sorted = vs.clone();
+        sorted.sort();
+
+        (vs.iter().copied().collect(), vs, sorted)
+    }
+
+    #[test]
+    fn queries() {
+        let (tree, vs, sorted) = get_data();
+
+        assert_eq!(tree.len(), vs.len());
+        assert_eq!(tree.iter().len(), vs.len());
+        assert_eq!(tree.iter().copied().collect::<Vec<_>>(), sorted);
+        assert_eq!(tree.minimum(), Some(&1));
+        assert_eq!(tree.maximum(), Some(&9));
+        assert!(vs.iter().all(|v| tree.search(v)));
+        assert!(!tree.search(&3));
+        assert!(!tree.search(&10));
+
+        let empty = ArenaBst::<usize>::new();
+
+        assert_eq!(empty.minimum(), None);
+        assert_eq!(empty.iter().next(), None);
+        assert!(!empty.search(&1));
+    }
+
+    #[test]
+    fn remove() {
+        let (mut tree, vs, mut sorted) = get_data();
+
+        assert_eq!(tree.remove(&100), None);
+
+        for v in &vs {
+            assert_eq!(tree.remove(v), Some(*v));
+
+            let i = sorted.binary_search(v).unwrap();
+            sorted.remove(i);
+
+            assert_eq!(tree.iter().copied().collect::<Vec<_>>(), sorted);
+        }
+
+        assert!(tree.is_empty());
+        assert_eq!(tree.remove(&5), None);
+    }
+
+    #[test]
+    fn reuses_slots() {
+        let (mut tree, vs, _) = get_data();
+
+        for v in [4, 5, 9] {
+            tree.remove(&v);
+        }
+
+        tree.insert(3);
+        tree.insert(8);
+        tree.insert(2);
+
+        assert_eq!(tree.slots.len(), vs.len());
+
+        tree.insert(0);
+
+        assert_eq!(tree.slots.len(), vs.len() + 1);
+        assert_eq!(tree.minimum(), Some(&0));
+
+        tree.clear();
+
+        assert!(tree.is_empty());
+        assert!(tree.slots.capacity() > vs.len());
+
+        tree.insert(1);
+
+        assert_eq!(tree.iter().collect::<Vec<_>>(), vec![&1]);
+    }
+
+    #[test]
+    fn clone() {
+        let (tree, _, sorted) = get_data();
+        let mut copy = tree.clone();
+
+        copy.remove(&9);
+        copy.insert(10);
+
+        assert_eq!(tree.iter().copied().collect::<Vec<_>>(), sorted);
+        assert_eq!(copy.maximum(), Some(&10));
+    }
+
+    #[test]
+    fn deep_tree() {
+        // ascending insertions make a spine that no operation walks
+        // recursively
+        let len = 5_000;
+        let mut tree: ArenaBst<_> = (0..len).collect();
+
+        assert_eq!(tree.maximum(), Some(&(len - 1)));
+        assert_eq!(tree.remove(&(len / 2)), Some(len / 2));
+        assert_eq!(tree.iter().count(), len - 1);
+
+        let copy = tree.clone();
+
+        drop(tree);
+
+        assert!(copy.search(&(len - 1)));
+    }
+}
@@ -0,0 +1,445 @@
+use std::{
+    cmp::{self, Ordering},
+    mem,
+};
+
+use crate::binary_tree::{self, BinaryNode, InOrder};
+
+/// A height-balanced binary search tree.
+///
+/// `AvlTree` offers the same query API as
+/// [`BinarySearchTree`](crate::binary_search_tree::BinarySearchTree), but
+/// rotates after every `insert` and `remove` so that the heights of the two
+/// subtrees of any node differ by at most one. The height of a tree holding
+/// `n` values is therefore bounded by roughly `1.44 * log2(n)`, whatever the
+/// insertion order.
+#[derive(Debug, Clone)]
+pub struct AvlTree<T>
+where
+    T: Ord,
+{
+    root: Link<T>,
+    len: usize,
+}
+
+type Link<T> = Option<Box<Node<T>>>;
+
+#[derive(Debug, Clone)]
+struct Node<T> {
+    value: T,
+    height: usize,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+impl<T> Node<T> {
+    fn new(value: T) -> Box<Self> {
+        Box::new(Node {
+            value,
+            height: 1,
+            left: None,
+            right: None,
+        })
+    }
+
+    fn update_height(&mut self) {
+        self.height = 1 + cmp::max(height(&self.left), height(&self.right));
+    }
+
+    fn balance_factor(&self) -> isize {
+        height(&self.left) as isize - height(&self.right) as isize
+    }
+}
+
+impl<T> BinaryNode for Node<T> {
+    type Value = T;
+
+    fn value(&self) -> &T {
+        &self.value
+    }
+
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+}
+
+fn height<T>(link: &Link<T>) -> usize {
+    link.as_ref()
+        .map_or(0, |node| node.height)
+}
+
+fn balance_factor<T>(link: &Link<T>) -> isize {
+    link.as_ref()
+        .map_or(0, |node| node.balance_factor())
+}
+
+fn rotate_left<T>(mut node: Box<Node<T>>) -> Box<Node<T>> {
+    let mut pivot = node.right.take().unwrap();
+
+    node.right = pivot.left.take();
+    node.update_height();
+
+    pivot.left = Some(node);
+    pivot.update_height();
+
+    pivot
+}
+
+fn rotate_right<T>(mut node: Box<Node<T>>) -> Box<Node<T>> {
+    let mut pivot = node.left.take().unwrap();
+
+    node.left = pivot.right.take();
+    node.update_height();
+
+    pivot.right = Some(node);
+    pivot.update_height();
+
+    pivot
+}
+
+/// Restore the AVL invariant at `node`, assuming both of its subtrees
+/// already satisfy it and their heights differ by at most two.
+fn rebalance<T>(mut node: Box<Node<T>>) -> Box<Node<T>> {
+    node.update_height();
+
+    match node.balance_factor() {
+        2 => {
+            if balance_factor(&node.left) < 0 {
+                node.left = node.left.take().map(rotate_left);
+            }
+
+            rotate_right(node)
+        },
+        -2 => {
+            if balance_factor(&node.right) > 0 {
+                node.right = node.right.take().map(rotate_right);
+            }
+
+            rotate_left(node)
+        },
+        _ => node,
+    }
+}
+
+fn insert<T>(link: Link<T>, value: T) -> Box<Node<T>>
+where
+    T: Ord,
+{
+    match link {
+        Some(mut node) => {
+            if node.value > value {
+                node.left = Some(insert(node.left.take(), value));
+            } else {
+                node.right = Some(insert(node.right.take(), value));
+            }
+
+            rebalance(node)
+        },
+        None => Node::new(value),
+    }
+}
+
+fn remove<T>(link: &mut Link<T>, value: &T) -> Option<T>
+where
+    T: Ord,
+{
+    let mut node = link.take()?;
+
+    let removed = match node.value.cmp(value) {
+        Ordering::Greater => remove(&mut node.left, value),
+        Ordering::Less => remove(&mut node.right, value),
+        Ordering::Equal => match (node.left.take(), node.right.take()) {
+            (None, None) => return Some(node.value),
+            (Some(child), None) | (None, Some(child)) => {
+                *link = Some(child);
+
+                return Some(node.value);
+            },
+            (Some(left), Some(right)) => {
+                let (successor, right) = remove_min(right);
+
+                node.left = Some(left);
+                node.right = right;
+
+                Some(mem::replace(&mut node.value, successor))
+            },
+        },
+    };
+
+    *link = Some(rebalance(node));
+
+    removed
+}
+
+fn remove_min<T>(mut node: Box<Node<T>>) -> (T, Link<T>) {
+    match node.left.take() {
+        Some(left) => {
+            let (min, left) = remove_min(left);
+
+            node.left = left;
+
+            (min, Some(rebalance(node)))
+        },
+        None => (node.value, node.right),
+    }
+}
+
+impl<T> Default for AvlTree<T>
+where
+    T: Ord,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> AvlTree<T>
+where
+    T: Ord,
+{
+    pub fn new() -> Self {
+        AvlTree { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the number of nodes on the longest path from the root to a
+    /// leaf, or 0 for an empty tree.
+    pub fn height(&self) -> usize {
+        height(&self.root)
+    }
+
+    pub fn insert(&mut self, value: T) {
+        self.root = Some(insert(self.root.take(), value));
+        self.len += 1;
+    }
+
+    /// Remove one occurrence of `value` from the tree and return it, or
+    /// `None` if the tree does not contain it.
+    pub fn remove(&mut self, value: &T) -> Option<T> {
+        let removed = remove(&mut self.root, value);
+
+        if removed.is_some() {
+            self.len -= 1;
+        }
+
+        removed
+    }
+
+    pub fn search(&self, value: &T) -> bool {
+        let mut node = self.root.as_deref();
+
+        while let Some(n) = node {
+            node = match n.value.cmp(value) {
+                Ordering::Equal => return true,
+                Ordering::Greater => n.left.as_deref(),
+                Ordering::Less => n.right.as_deref(),
+            };
+        }
+
+        false
+    }
+
+    pub fn minimum(&self) -> Option<&T> {
+        self.root
+            .as_deref()
+            .map(binary_tree::minimum)
+    }
+
+    pub fn maximum(&self) -> Option<&T> {
+        self.root
+            .as_deref()
+            .map(binary_tree::maximum)
+    }
+
+    pub fn floor(&self, value: &T) -> Option<&T> {
+        let mut node = self.root.as_deref();
+        let mut floor = None;
+
+        while let Some(n) = node {
+            node = match n.value.cmp(value) {
+                Ordering::Equal => return Some(&n.value),
+                Ordering::Greater => n.left.as_deref(),
+                Ordering::Less => {
+                    floor = Some(&n.value);
+                    n.right.as_deref()
+                },
+            };
+        }
+
+        floor
+    }
+
+    pub fn ceil(&self, value: &T) -> Option<&T> {
+        let mut node = self.root.as_deref();
+        let mut ceil = None;
+
+        while let Some(n) = node {
+            node = match n.value.cmp(value) {
+                Ordering::Equal => return Some(&n.value),
+                Ordering::Greater => {
+                    ceil = Some(&n.value);
+                    n.left.as_deref()
+                },
+                Ordering::Less => n.right.as_deref(),
+            };
+        }
+
+        ceil
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter(InOrder::new(self.root.as_deref()))
+    }
+}
+
+impl<T> From<Vec<T>> for AvlTree<T>
+where
+    T: Ord,
+{
+    fn from(value: Vec<T>) -> Self {
+        let mut tree = Self::new();
+
+        for v in value {
+            tree.insert(v);
+        }
+
+        tree
+    }
+}
+
+pub struct Iter<'a, T>(InOrder<'a, Node<T>>);
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_data() -> (AvlTree<usize>, Vec<usize>, Vec<usize>) {
+        let vs = vec![5, 1, 4, 4, 4, 6, 5, 4, 5, 6, 5, 9, 7, 6];
+        let copy = vs.clone();
+        let mut sorted = vs.clone();
+        sorted.sort();
+
+        (AvlTree::from(copy), vs, sorted)
+    }
+
+    // returns the height of the subtree, panicking if any node is out of
+    // balance or carries a stale height
+    fn check_balanced<T>(link: &Link<T>) -> usize {
+        match link {
+            Some(node) => {
+                let left = check_balanced(&node.left);
+                let right = check_balanced(&node.right);
+
+                assert!(left.abs_diff(right) <= 1);
+                assert_eq!(node.height, 1 + cmp::max(left, right));
+
+                node.height
+            },
+            None => 0,
+        }
+    }
+
+    fn max_height(len: usize) -> usize {
+        // the classic AVL bound: h < 1.4405 * log2(n + 2) - 0.3277
+        (1.4405 * ((len + 2) as f64).log2()) as usize
+    }
+
+    #[test]
+    fn queries() {
+        let (tree, vs, sorted) = get_data();
+
+        assert_eq!(tree.len(), vs.len());
+        assert_eq!(tree.iter().copied().collect::<Vec<_>>(), sorted);
+        assert_eq!(tree.minimum(), Some(&1));
+        assert_eq!(tree.maximum(), Some(&9));
+
+        for v in &vs {
+            assert!(tree.search(v));
+            assert_eq!(tree.floor(v), Some(v));
+            assert_eq!(tree.ceil(v), Some(v));
+        }
+
+        assert!(!tree.search(&10));
+        assert_eq!(tree.floor(&0), None);
+        assert_eq!(tree.floor(&8), Some(&7));
+        assert_eq!(tree.ceil(&3), Some(&4));
+        assert_eq!(tree.ceil(&10), None);
+    }
+
+    #[test]
+    fn sorted_inserts_stay_logarithmic() {
+        let len = 100_000;
+        let mut ascending = AvlTree::new();
+        let mut descending = AvlTree::new();
+
+        for v in 0..len {
+            ascending.insert(v);
+            descending.insert(len - v);
+        }
+
+        for tree in [&ascending, &descending] {
+            check_balanced(&tree.root);
+
+            assert_eq!(tree.len(), len);
+            assert!(tree.height() <= max_height(len));
+        }
+
+        // a full binary tree is the best any shape can do
+        assert!(ascending.height() >= (len as f64).log2().ceil() as usize);
+    }
+
+    #[test]
+    fn remove() {
+        let (mut tree, vs, mut sorted) = get_data();
+
+        assert_eq!(tree.remove(&100), None);
+
+        for v in &vs {
+            assert_eq!(tree.remove(v), Some(*v));
+            check_balanced(&tree.root);
+
+            let i = sorted.binary_search(v).unwrap();
+            sorted.remove(i);
+
+            assert_eq!(tree.iter().copied().collect::<Vec<_>>(), sorted);
+        }
+
+        assert!(tree.is_empty());
+        assert_eq!(tree.height(), 0);
+    }
+
+    #[test]
+    fn removals_stay_logarithmic() {
+        let len = 10_000;
+        let mut tree = AvlTree::from((0..len).collect::<Vec<_>>());
+
+        for v in (0..len).filter(|v| v % 3 != 0) {
+            assert_eq!(tree.remove(&v), Some(v));
+        }
+
+        check_balanced(&tree.root);
+
+        assert_eq!(tree.len(), len / 3 + 1);
+        assert!(tree.height() <= max_height(tree.len()));
+        assert!(tree.iter().all(|v| v % 3 == 0));
+    }
+}
@@ -1,21 +1,210 @@
-use std::{cmp::Ordering, collections::VecDeque};
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    collections::VecDeque,
+    fmt::{self, Display, Write},
+    iter::{self, Peekable},
+    marker::PhantomData,
+    mem,
+    ops::{
+        BitAnd, BitOr, BitXor, Bound, Deref, DerefMut, Not, RangeBounds, Sub,
+    },
+    thread,
+};
 
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::binary_tree::{self, BinaryNode};
+
+/// An unbalanced binary search tree.
+///
+/// How equal values are stored is chosen by the `P` type parameter, see
+/// [`DuplicatePolicy`], and how values are ordered by the `C` type
+/// parameter, see [`Comparator`].
 #[derive(Debug, Clone)]
-pub struct BinarySearchTree<T>
+pub struct BinarySearchTree<T, P = KeepDuplicates, C = NaturalOrder> {
+    root: Node<T>,
+    policy: PhantomData<P>,
+    compare: C,
+}
+
+/// A summary of the shape of a [`BinarySearchTree`], as returned by
+/// [`BinarySearchTree::stats`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeStats {
+    /// The number of values, counting every occurrence.
+    pub len: usize,
+    pub node_count: usize,
+    pub height: usize,
+    /// The number of nodes at each depth, the root being at depth 0.
+    pub depth_histogram: Vec<usize>,
+    /// The height of the left subtree of the root minus that of the right
+    /// one.
+    pub balance_factor: isize,
+    /// The largest difference between the heights of the two subtrees of
+    /// any node.
+    pub max_imbalance: usize,
+}
+
+/// What [`BinarySearchTree::insert`] does with a value equal to one that is
+/// already in the tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnDuplicate {
+    /// Store the value in a node of its own.
+    Keep,
+    /// Drop the value and leave the tree unchanged.
+    Reject,
+    /// Bump the multiplicity counter of the existing node.
+    Count,
+}
+
+pub trait DuplicatePolicy<T> {
+    const ON_DUPLICATE: OnDuplicate;
+
+    /// Produce one more occurrence of a counted value, to hand it out by
+    /// value while others remain in the tree. Only called when
+    /// `ON_DUPLICATE` is `OnDuplicate::Count`.
+    fn copy(value: &T) -> T;
+}
+
+/// Store every duplicate in a node of its own, to the right of the equal
+/// values inserted before it. This is the default policy.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeepDuplicates;
+
+/// Ignore values that are already in the tree, giving it set semantics.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RejectDuplicates;
+
+/// Store each distinct value once, with a count of its occurrences.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CountDuplicates;
+
+impl<T> DuplicatePolicy<T> for KeepDuplicates {
+    const ON_DUPLICATE: OnDuplicate = OnDuplicate::Keep;
+
+    fn copy(_: &T) -> T {
+        unreachable!("duplicates are kept in nodes of their own")
+    }
+}
+
+impl<T> DuplicatePolicy<T> for RejectDuplicates {
+    const ON_DUPLICATE: OnDuplicate = OnDuplicate::Reject;
+
+    fn copy(_: &T) -> T {
+        unreachable!("duplicates are rejected")
+    }
+}
+
+impl<T> DuplicatePolicy<T> for CountDuplicates
 where
-    T: Ord,
+    T: Clone,
+{
+    const ON_DUPLICATE: OnDuplicate = OnDuplicate::Count;
+
+    fn copy(value: &T) -> T {
+        value.clone()
+    }
+}
+
+/// A total order on values of type `T`, which a [`BinarySearchTree`] uses
+/// in place of `Ord`.
+///
+/// Any `Fn(&T, &T) -> Ordering` is a comparator, so floats can be ordered
+/// with `|a: &f64, b: &f64| a.total_cmp(b)`, or strings in reverse with
+/// `|a: &String, b: &String| b.cmp(a)`.
+pub trait Comparator<T>
+where
+    T: ?Sized,
+{
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+}
+
+/// Order values by their `Ord` implementation. This is the default
+/// comparator.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NaturalOrder;
+
+impl<T> Comparator<T> for NaturalOrder
+where
+    T: Ord + ?Sized,
+{
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+impl<T, F> Comparator<T> for F
+where
+    T: ?Sized,
+    F: Fn(&T, &T) -> Ordering,
 {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
+
+/// How far apart two values are, as used by [`BinarySearchTree::nearest`].
+/// The distance must grow as values move apart in the ordering of the tree.
+pub trait Distance {
+    type Output: Ord;
+
+    fn distance(&self, other: &Self) -> Self::Output;
+}
+
+macro_rules! impl_distance {
+    ($($t:ty => $output:ty),* $(,)?) => {
+        $(
+            impl Distance for $t {
+                type Output = $output;
+
+                fn distance(&self, other: &Self) -> $output {
+                    self.abs_diff(*other)
+                }
+            }
+        )*
+    };
+}
+
+impl_distance! {
+    u8 => u8,
+    u16 => u16,
+    u32 => u32,
+    u64 => u64,
+    u128 => u128,
+    usize => usize,
+    i8 => u8,
+    i16 => u16,
+    i32 => u32,
+    i64 => u64,
+    i128 => u128,
+    isize => usize,
+}
+
+#[derive(Debug)]
+struct Node<T> {
     value: Option<T>,
-    left: Option<Box<BinarySearchTree<T>>>,
-    right: Option<Box<BinarySearchTree<T>>>,
+    left: Link<T>,
+    right: Link<T>,
+    // number of occurrences of `value` stored in this node
+    count: usize,
+    // number of values in this subtree, counting every occurrence
+    size: usize,
 }
 
-impl<T> Default for BinarySearchTree<T>
+type Link<T> = Option<Box<Node<T>>>;
+
+impl<T, P, C> Default for BinarySearchTree<T, P, C>
 where
-    T: Ord,
+    P: DuplicatePolicy<T>,
+    C: Comparator<T> + Default,
 {
     fn default() -> Self {
-        Self::new()
+        BinarySearchTree {
+            root: Node::new(),
+            policy: PhantomData,
+            compare: C::default(),
+        }
     }
 }
 
@@ -24,251 +213,4239 @@ where
     T: Ord,
 {
     pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<T, P> BinarySearchTree<T, P>
+where
+    T: Ord,
+    P: DuplicatePolicy<T>,
+{
+    /// Create an empty tree that handles duplicates following `policy`,
+    /// e.g. `BinarySearchTree::with_policy(RejectDuplicates)`.
+    pub fn with_policy(_policy: P) -> Self {
+        Self::default()
+    }
+}
+
+impl<T, C> BinarySearchTree<T, KeepDuplicates, C>
+where
+    C: Comparator<T>,
+{
+    /// Create an empty tree that orders values with `compare`, see
+    /// [`Comparator`].
+    pub fn with_comparator(compare: C) -> Self {
+        Self::with_policy_and_comparator(KeepDuplicates, compare)
+    }
+}
+
+impl<T, P, C> BinarySearchTree<T, P, C>
+where
+    P: DuplicatePolicy<T>,
+    C: Comparator<T>,
+{
+    /// Create an empty tree that handles duplicates following `policy` and
+    /// orders values with `compare`.
+    pub fn with_policy_and_comparator(_policy: P, compare: C) -> Self {
         BinarySearchTree {
-            value: None,
-            left: None,
-            right: None,
+            root: Node::new(),
+            policy: PhantomData,
+            compare,
         }
     }
 
-    pub fn search(&self, value: &T) -> bool {
-        self.value
-            .as_ref()
-            .map_or(false, |key| match key.cmp(value) {
-                Ordering::Equal => true,
-                Ordering::Greater => self
-                    .left
-                    .as_ref()
-                    .map_or(false, |node| node.search(value)),
-                Ordering::Less => self
-                    .right
-                    .as_ref()
-                    .map_or(false, |node| node.search(value)),
-            })
+    /// Return whether the tree contains `value`, which may be any borrowed
+    /// form of `T` that the comparator can order, e.g. `&str` for `String`.
+    pub fn search<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.search_by(|key| {
+            self.compare
+                .compare(key.borrow(), value)
+        })
+        .is_some()
     }
 
-    pub fn insert(&mut self, value: T) {
-        if self.value.is_none() {
-            self.value = Some(value)
-        } else {
-            let key = self.value.as_ref().unwrap();
-
-            let target_node = if *key > value {
-                &mut self.left
-            } else {
-                &mut self.right
-            };
+    /// Insert `value` into the tree, following the duplicate policy `P`.
+    /// Return whether the tree grew, which is only ever `false` when `P`
+    /// rejects a duplicate.
+    pub fn insert(&mut self, value: T) -> bool {
+        self.root
+            .insert::<P, C>(value, &self.compare)
+            .1
+    }
 
-            match target_node {
-                Some(ref mut node) => {
-                    node.insert(value);
-                },
-                None => {
-                    let mut node = Self::new();
-                    node.insert(value);
-                    *target_node = Some(Box::new(node));
-                },
-            }
-        }
+    /// Return the number of occurrences of `value` in the tree.
+    pub fn count<Q>(&self, value: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.count_range((Bound::Included(value), Bound::Included(value)))
     }
 
     pub fn minimum(&self) -> Option<&T> {
-        self.left
-            .as_ref()
-            .map_or(self.value.as_ref(), |node| node.minimum())
+        self.root_node()
+            .map(binary_tree::minimum)
     }
 
     pub fn maximum(&self) -> Option<&T> {
-        self.right
-            .as_ref()
-            .map_or(self.value.as_ref(), |node| node.maximum())
+        self.root_node()
+            .map(binary_tree::maximum)
     }
 
-    pub fn floor(&self, value: &T) -> Option<&T> {
-        let key = self.value.as_ref()?;
+    pub fn floor<Q>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.floor_by(|key| {
+            self.compare
+                .compare(key.borrow(), value)
+        })
+    }
 
-        match key.cmp(value) {
-            Ordering::Equal => Some(key),
-            Ordering::Less => self
-                .right
-                .as_ref()
-                .map_or(Some(key), |node| node.floor(value).or(Some(key))),
-            Ordering::Greater => self
-                .left
-                .as_ref()
-                .and_then(|node| node.floor(value)),
-        }
+    pub fn ceil<Q>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.ceil_by(|key| {
+            self.compare
+                .compare(key.borrow(), value)
+        })
     }
 
-    pub fn ceil(&self, value: &T) -> Option<&T> {
-        let key = self.value.as_ref()?;
+    /// Return the greatest value in the tree that is strictly less than
+    /// `value`.
+    pub fn predecessor<Q>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        // treating equal values as greater skips past them
+        self.floor_by(|key| {
+            self.compare
+                .compare(key.borrow(), value)
+                .then(Ordering::Greater)
+        })
+    }
 
-        match key.cmp(value) {
-            Ordering::Equal => Some(key),
-            Ordering::Less => self
-                .right
-                .as_ref()
-                .and_then(|node| node.ceil(value)),
-            Ordering::Greater => self
-                .left
-                .as_ref()
-                .map_or(Some(key), |node| node.ceil(value).or(Some(key))),
+    /// Return the least value in the tree that is strictly greater than
+    /// `value`.
+    pub fn successor<Q>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.ceil_by(|key| {
+            self.compare
+                .compare(key.borrow(), value)
+                .then(Ordering::Less)
+        })
+    }
+
+    /// Return the value in the tree closest to `value`, preferring the
+    /// smaller one of two that are equally close.
+    pub fn nearest(&self, value: &T) -> Option<&T>
+    where
+        T: Distance,
+    {
+        match (self.floor(value), self.ceil(value)) {
+            (Some(floor), Some(ceil)) => {
+                if ceil.distance(value) < floor.distance(value) {
+                    Some(ceil)
+                } else {
+                    Some(floor)
+                }
+            },
+            (floor, ceil) => floor.or(ceil),
         }
     }
 
-    pub fn len(&self) -> usize {
-        self.iter().collect::<Vec<_>>().len()
+    /// Remove one occurrence of `value` from the tree and return it, or
+    /// `None` if the tree does not contain it.
+    pub fn remove<Q>(&mut self, value: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let compare = &self.compare;
+
+        self.root
+            .remove_by::<P, _>(&mut |key| compare.compare(key.borrow(), value))
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = &T> {
-        BinarySearchTreeIterator::new(self)
+    // The `*_by` helpers locate a value through `f`, which compares a stored
+    // value against the target (`stored.cmp(target)`) and must agree with
+    // the ordering of the tree. They let wrappers such as `BstMap` look
+    // values up by a part of `T`.
+
+    pub(crate) fn search_by<F>(&self, f: F) -> Option<&T>
+    where
+        F: FnMut(&T) -> Ordering,
+    {
+        self.root.search_by(f)
     }
 
-    fn values<'a>(&'a self, vs: &mut VecDeque<&'a T>) {
-        if self.left.is_some() {
-            self.left.as_ref().unwrap().values(vs);
-        }
+    /// Callers must not change the position of the returned value in the
+    /// ordering of the tree.
+    pub(crate) fn search_by_mut<F>(&mut self, f: F) -> Option<&mut T>
+    where
+        F: FnMut(&T) -> Ordering,
+    {
+        self.root.search_by_mut(f)
+    }
 
-        if self.value.is_some() {
-            vs.push_back(self.value.as_ref().unwrap());
-        }
+    pub(crate) fn floor_by<F>(&self, mut f: F) -> Option<&T>
+    where
+        F: FnMut(&T) -> Ordering,
+    {
+        let mut node = Some(&self.root);
+        let mut floor = None;
 
-        if self.right.is_some() {
-            self.right.as_ref().unwrap().values(vs);
+        while let Some(n) = node {
+            let key = n.value.as_ref()?;
+
+            node = match f(key) {
+                Ordering::Equal => return Some(key),
+                Ordering::Greater => n.left.as_deref(),
+                Ordering::Less => {
+                    floor = Some(key);
+                    n.right.as_deref()
+                },
+            };
         }
+
+        floor
     }
-}
 
-impl<T> From<Vec<T>> for BinarySearchTree<T>
-where
-    T: Ord,
-{
-    fn from(value: Vec<T>) -> Self {
-        let mut tree = Self::new();
+    pub(crate) fn ceil_by<F>(&self, mut f: F) -> Option<&T>
+    where
+        F: FnMut(&T) -> Ordering,
+    {
+        let mut node = Some(&self.root);
+        let mut ceil = None;
 
-        for v in value {
-            tree.insert(v);
+        while let Some(n) = node {
+            let key = n.value.as_ref()?;
+
+            node = match f(key) {
+                Ordering::Equal => return Some(key),
+                Ordering::Greater => {
+                    ceil = Some(key);
+                    n.left.as_deref()
+                },
+                Ordering::Less => n.right.as_deref(),
+            };
         }
 
-        tree
+        ceil
     }
-}
 
-struct BinarySearchTreeIterator<'a, T> {
-    values: VecDeque<&'a T>,
-}
+    /// Insert `value` and return a reference to the stored value, which is
+    /// an older equal value if `P` does not keep duplicates.
+    pub(crate) fn insert_mut(&mut self, value: T) -> &mut T {
+        self.root
+            .insert::<P, C>(value, &self.compare)
+            .0
+    }
 
-impl<'a, T> BinarySearchTreeIterator<'a, T>
-where
-    T: Ord,
-{
-    fn new(tree: &'a BinarySearchTree<T>) -> Self {
-        let mut vs = VecDeque::new();
+    pub(crate) fn remove_by<F>(&mut self, mut f: F) -> Option<T>
+    where
+        F: FnMut(&T) -> Ordering,
+    {
+        self.root.remove_by::<P, F>(&mut f)
+    }
 
-        tree.values(&mut vs);
+    /// Remove and return the smallest value in the tree.
+    pub fn remove_min(&mut self) -> Option<T> {
+        self.root.remove_min::<P>()
+    }
 
-        BinarySearchTreeIterator { values: vs }
+    /// Remove and return the largest value in the tree.
+    pub fn remove_max(&mut self) -> Option<T> {
+        self.root.remove_max::<P>()
     }
-}
 
-impl<'a, T> Iterator for BinarySearchTreeIterator<'a, T> {
-    type Item = &'a T;
+    /// Keep only the values for which `f` returns `true`. `f` is called
+    /// once per value, in ascending order, and the remaining values are
+    /// relinked into a balanced tree.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.relink(|node| {
+            let value = node.value.as_ref().unwrap();
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.values.pop_front()
+            node.count = (0..node.count)
+                .filter(|_| f(value))
+                .count();
+
+            node.count > 0
+        });
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    pub fn len(&self) -> usize {
+        self.root.size
+    }
 
-    fn get_data() -> (BinarySearchTree<usize>, Vec<usize>, Vec<usize>) {
-        let vs = vec![5, 1, 4, 4, 4, 6, 5, 4, 5, 6, 5, 9, 7, 6];
-        let copy = vs.clone();
-        let mut sorted = vs.clone();
-        sorted.sort();
+    pub fn is_empty(&self) -> bool {
+        self.root.value.is_none()
+    }
 
-        (BinarySearchTree::from(copy), vs, sorted)
+    /// Iterate in ascending order over the values within `range`, skipping
+    /// the subtrees that lie entirely outside of it.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, T, R, C, Q>
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        R: RangeBounds<Q>,
+        C: Comparator<Q>,
+    {
+        Range {
+            walk: InOrder::new(&self.root, |value| *value),
+            range,
+            compare: &self.compare,
+            bound: PhantomData,
+        }
     }
 
-    #[test]
-    fn iter() {
-        let (tree, _, sorted) = get_data();
-        // should iter through the items in ascending order
-        let v = tree
-            .iter()
-            .map(|v| *v)
-            .collect::<Vec<usize>>();
+    /// Return the number of values within `range`, in O(height) time.
+    pub fn count_range<Q, R>(&self, range: R) -> usize
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        R: RangeBounds<Q>,
+        C: Comparator<Q>,
+    {
+        let compare = |key: &T, bound: &Q| {
+            self.compare
+                .compare(key.borrow(), bound)
+        };
 
-        println!("v = {:?}", v);
+        let before_start = match range.start_bound() {
+            Bound::Included(start) => {
+                self.count_while(|key| compare(key, start).is_lt())
+            },
+            Bound::Excluded(start) => {
+                self.count_while(|key| compare(key, start).is_le())
+            },
+            Bound::Unbounded => 0,
+        };
 
-        assert_eq!(v, sorted);
+        let before_end = match range.end_bound() {
+            Bound::Included(end) => {
+                self.count_while(|key| compare(key, end).is_le())
+            },
+            Bound::Excluded(end) => {
+                self.count_while(|key| compare(key, end).is_lt())
+            },
+            Bound::Unbounded => self.len(),
+        };
+
+        before_end.saturating_sub(before_start)
     }
 
-    #[test]
-    fn len() {
-        let (tree, vs, _) = get_data();
+    /// Remove every value within `range` and return how many were removed.
+    ///
+    /// The tree is split around the range and joined back together, in
+    /// O(height) time plus the time to drop the removed values.
+    pub fn remove_range<Q, R>(&mut self, range: R) -> usize
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        R: RangeBounds<Q>,
+        C: Comparator<Q>,
+    {
+        let root = Node::into_link(mem::replace(&mut self.root, Node::new()));
+        let compare = &self.compare;
+        let (less, rest) =
+            Node::split(root, |key| !position_in(&range, key, compare).0);
+        let (within, greater) =
+            Node::split(rest, |key| position_in(&range, key, compare).1);
 
-        assert_eq!(tree.len(), vs.len());
+        self.root = Node::from_link(Node::join(less, greater));
+
+        size(&within)
     }
 
-    #[test]
-    fn search() {
-        let (tree, vs, _) = get_data();
+    /// Return the number of values in the tree that are less than `value`.
+    pub fn rank<Q>(&self, value: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.count_while(|key| {
+            self.compare
+                .compare(key.borrow(), value)
+                .is_lt()
+        })
+    }
 
-        for v in &vs {
-            assert_eq!(tree.search(v), true);
+    /// Return the `k`th smallest value in the tree, counting from zero.
+    pub fn select(&self, mut k: usize) -> Option<&T> {
+        let mut node = &self.root;
+
+        loop {
+            let left = size(&node.left);
+
+            node = if k < left {
+                node.left.as_deref()?
+            } else if k < left + node.count {
+                return node.value.as_ref();
+            } else {
+                k -= left + node.count;
+                node.right.as_deref()?
+            };
         }
+    }
 
-        let falsy = [10, 13, 15, 100];
+    /// Return the median of the tree, taking the lower of the two middle
+    /// values when the tree holds an even number of them.
+    pub fn median(&self) -> Option<&T> {
+        self.select(self.len().checked_sub(1)? / 2)
+    }
 
-        for v in &falsy {
-            assert_eq!(tree.search(v), false);
+    /// Count the values for which `f` returns `true`, where `f` must hold
+    /// for a prefix of the values in ascending order.
+    fn count_while<F>(&self, mut f: F) -> usize
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let mut node = Some(&self.root);
+        let mut count = 0;
+
+        while let Some(n) = node {
+            let Some(key) = n.value.as_ref() else {
+                break;
+            };
+
+            node = if f(key) {
+                count += size(&n.left) + n.count;
+                n.right.as_deref()
+            } else {
+                n.left.as_deref()
+            };
+        }
+
+        count
+    }
+
+    /// Iterate over the values of the tree in ascending order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            walk: InOrder::new(&self.root, |value| *value),
+            len: self.len(),
         }
     }
 
-    #[test]
-    fn minimum() {
-        let (tree, vs, _) = get_data();
+    // The traversals below yield a counted value once per occurrence, like
+    // `iter`, before moving on to the next node.
 
-        assert_eq!(tree.minimum(), vs.iter().min());
+    /// Iterate over the values of the tree with each node before its left
+    /// subtree, and the left subtree before the right one.
+    pub fn iter_preorder(&self) -> PreOrder<'_, T> {
+        PreOrder {
+            stack: self.root_node().into_iter().collect(),
+            current: Occurrences::default(),
+            len: self.len(),
+        }
     }
 
-    #[test]
-    fn maximum() {
-        let (tree, vs, _) = get_data();
+    /// Iterate over the values of the tree with the left subtree of each
+    /// node first, then the right subtree, then the node itself.
+    pub fn iter_postorder(&self) -> PostOrder<'_, T> {
+        PostOrder {
+            stack: self
+                .root_node()
+                .map(|node| (node, false))
+                .into_iter()
+                .collect(),
+            current: Occurrences::default(),
+            len: self.len(),
+        }
+    }
 
-        assert_eq!(tree.maximum(), vs.iter().max());
+    /// Iterate breadth-first over the values of the tree, from the root
+    /// down and from left to right within a level, yielding the depth of
+    /// each value alongside it. The root is at depth 0.
+    pub fn iter_level_order(&self) -> LevelOrder<'_, T> {
+        LevelOrder {
+            queue: self
+                .root_node()
+                .map(|node| (0, node))
+                .into_iter()
+                .collect(),
+            current: Occurrences::default(),
+            len: self.len(),
+        }
     }
 
-    #[test]
-    fn floor() {
-        let (tree, vs, _) = get_data();
+    /// Return the root node, or `None` for an empty tree.
+    fn root_node(&self) -> Option<&Node<T>> {
+        self.root
+            .value
+            .as_ref()
+            .map(|_| &self.root)
+    }
 
-        assert_eq!(tree.floor(&100), Some(&9));
-        assert_eq!(tree.floor(&0), None);
-        assert_eq!(tree.floor(&2), Some(&1));
-        assert_eq!(tree.floor(&8), Some(&7));
+    /// Return the number of nodes on the longest path from the root to a
+    /// leaf, or 0 for an empty tree.
+    pub fn height(&self) -> usize {
+        self.fold_heights(|_, _| {})
+    }
 
-        for v in &vs {
-            // tree.floor should return the same value if it exists in the tree
-            assert_eq!(tree.floor(v), Some(v));
+    /// Return the number of nodes in the tree, which is less than `len` when
+    /// `P` counts duplicates.
+    pub fn node_count(&self) -> usize {
+        let mut count = 0;
+
+        self.visit_depths(|_| count += 1);
+
+        count
+    }
+
+    /// Return whether the heights of the two subtrees of every node differ
+    /// by at most one.
+    pub fn is_balanced(&self) -> bool {
+        let mut balanced = true;
+
+        self.fold_heights(|left, right| balanced &= left.abs_diff(right) <= 1);
+
+        balanced
+    }
+
+    /// Return whether the tree is ordered by `C`, its counters follow `P`,
+    /// and every node knows the size of its subtree.
+    pub fn is_valid_bst(&self) -> bool {
+        self.check_invariants().is_ok()
+    }
+
+    /// Summarize the shape of the tree.
+    pub fn stats(&self) -> TreeStats {
+        let mut depth_histogram = Vec::new();
+        let mut balance_factor = 0;
+        let mut max_imbalance = 0;
+
+        self.visit_depths(|depth| {
+            if depth == depth_histogram.len() {
+                depth_histogram.push(0);
+            }
+
+            depth_histogram[depth] += 1;
+        });
+
+        // the root is folded last
+        let height = self.fold_heights(|left, right| {
+            balance_factor = left as isize - right as isize;
+            max_imbalance = max_imbalance.max(left.abs_diff(right));
+        });
+
+        TreeStats {
+            len: self.len(),
+            node_count: depth_histogram.iter().sum(),
+            height,
+            depth_histogram,
+            balance_factor,
+            max_imbalance,
         }
     }
-    #[test]
-    fn ceil() {
-        let (tree, vs, _) = get_data();
 
-        assert_eq!(tree.ceil(&100), None);
-        assert_eq!(tree.ceil(&10), None);
-        assert_eq!(tree.ceil(&0), Some(&1));
-        assert_eq!(tree.ceil(&3), Some(&4));
-        assert_eq!(tree.ceil(&8), Some(&9));
+    /// Render the tree in the Graphviz DOT language, with a point for every
+    /// missing child if `null_leaves` is set. Counted values are labelled
+    /// with their count.
+    pub fn to_dot(&self, null_leaves: bool) -> String
+    where
+        T: Display,
+    {
+        let mut dot = String::from("digraph {\n");
+        let mut stack: Vec<_> = self
+            .root_node()
+            .map(|node| (0, node))
+            .into_iter()
+            .collect();
+        let mut next_id = 1;
 
-        for v in &vs {
-            // tree.ceil should return the same value if it exists in the tree
-            assert_eq!(tree.ceil(v), Some(v));
+        while let Some((id, node)) = stack.pop() {
+            let label = node
+                .label()
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"");
+
+            writeln!(dot, "    n{id} [label=\"{label}\"];").unwrap();
+
+            for child in [&node.left, &node.right] {
+                match child {
+                    Some(child) => {
+                        writeln!(dot, "    n{id} -> n{next_id};").unwrap();
+                        stack.push((next_id, child));
+                    },
+                    None if null_leaves => {
+                        writeln!(dot, "    null{next_id} [shape=point];")
+                            .unwrap();
+                        writeln!(dot, "    n{id} -> null{next_id};").unwrap();
+                    },
+                    None => continue,
+                }
+
+                next_id += 1;
+            }
+        }
+
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    /// Call `f` with the depth of every node, the root being at depth 0.
+    fn visit_depths<F>(&self, mut f: F)
+    where
+        F: FnMut(usize),
+    {
+        let mut stack: Vec<_> = self
+            .root_node()
+            .map(|node| (0, node))
+            .into_iter()
+            .collect();
+
+        while let Some((depth, node)) = stack.pop() {
+            f(depth);
+
+            for child in [&node.left, &node.right] {
+                stack.extend(
+                    child
+                        .as_deref()
+                        .map(|child| (depth + 1, child)),
+                );
+            }
         }
     }
+
+    /// Call `f` with the heights of the left and right subtrees of every
+    /// node, children before their parents, and return the height of the
+    /// tree.
+    fn fold_heights<F>(&self, mut f: F) -> usize
+    where
+        F: FnMut(usize, usize),
+    {
+        let mut stack: Vec<_> = self
+            .root_node()
+            .map(|node| (node, false))
+            .into_iter()
+            .collect();
+        // the heights of the subtrees walked so far whose parents have not
+        // been reached yet
+        let mut heights = Vec::new();
+
+        while let Some((node, queued)) = stack.pop() {
+            if queued {
+                let mut height = |child: &Link<T>| match child {
+                    Some(_) => heights.pop().unwrap(),
+                    None => 0,
+                };
+                let right = height(&node.right);
+                let left = height(&node.left);
+
+                f(left, right);
+                heights.push(1 + left.max(right));
+            } else {
+                stack.push((node, true));
+
+                for child in [&node.right, &node.left] {
+                    stack.extend(
+                        child
+                            .as_deref()
+                            .map(|child| (child, false)),
+                    );
+                }
+            }
+        }
+
+        heights.pop().unwrap_or(0)
+    }
+
+    /// Check that the tree is ordered, that its counters follow `P` and
+    /// that the subtree sizes add up.
+    fn check_invariants(&self) -> Result<(), &'static str> {
+        let strict = P::ON_DUPLICATE != OnDuplicate::Keep;
+        let mut previous = None;
+        let mut stack = Vec::new();
+        let mut node = self.root_node();
+
+        // walk the nodes in order, so that each is compared with the one
+        // before it
+        while node.is_some() || !stack.is_empty() {
+            while let Some(n) = node {
+                stack.push(n);
+                node = n.left.as_deref();
+            }
+
+            let n = stack.pop().unwrap();
+            let value = n.value.as_ref().unwrap();
+
+            match (n.count, P::ON_DUPLICATE) {
+                (0, _) => return Err("a node has a count of zero"),
+                (1, _) | (_, OnDuplicate::Count) => {},
+                _ => {
+                    return Err("the policy of the tree does not count values")
+                },
+            }
+
+            if n.size != n.count + size(&n.left) + size(&n.right) {
+                return Err("a node has the wrong subtree size");
+            }
+
+            match previous.map(|previous| self.compare.compare(previous, value))
+            {
+                Some(Ordering::Greater) => {
+                    return Err("the nodes are out of order")
+                },
+                Some(Ordering::Equal) if strict => {
+                    return Err("the policy of the tree rejects duplicates")
+                },
+                _ => previous = Some(value),
+            }
+
+            node = n.right.as_deref();
+        }
+
+        Ok(())
+    }
+
+    /// Split the tree in two, keeping the values less than `key` and
+    /// returning a tree with the others. No value is moved or re-inserted:
+    /// the tree is cut along the search path for `key`, in O(height) time.
+    pub fn split_off<Q>(&mut self, key: &Q) -> Self
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q> + Clone,
+    {
+        let root = Node::into_link(mem::replace(&mut self.root, Node::new()));
+        let (less, rest) = Node::split(root, |value| {
+            self.compare
+                .compare(value.borrow(), key)
+                .is_lt()
+        });
+
+        self.root = Node::from_link(less);
+
+        BinarySearchTree {
+            root: Node::from_link(rest),
+            policy: PhantomData,
+            compare: self.compare.clone(),
+        }
+    }
+
+    /// Move all the values of `other` into `self`, leaving `other` empty.
+    /// Both trees are assumed to order values the same way.
+    ///
+    /// When every value of `other` is greater than those of `self` (or
+    /// equal, if `P` keeps duplicates), the two trees are joined in
+    /// O(height) time below a new root, the largest value of `self`.
+    /// Otherwise the values of `other` are inserted one by one.
+    pub fn append(&mut self, other: &mut Self) {
+        let ordered = match (self.maximum(), other.minimum()) {
+            (Some(max), Some(min)) => match self.compare.compare(max, min) {
+                Ordering::Less => true,
+                Ordering::Equal => P::ON_DUPLICATE == OnDuplicate::Keep,
+                Ordering::Greater => false,
+            },
+            _ => true,
+        };
+
+        let other = mem::replace(&mut other.root, Node::new());
+
+        if !ordered {
+            for value in IntoIter::new::<P>(other) {
+                self.insert(value);
+            }
+
+            return;
+        }
+
+        let root = Node::into_link(mem::replace(&mut self.root, Node::new()));
+
+        self.root = Node::from_link(Node::join(root, Node::into_link(other)));
+    }
+
+    /// Iterate in ascending order over the values in `self` or `other`.
+    ///
+    /// Like the other set operations, this follows multiset semantics when
+    /// the trees hold duplicates: a value occurring `m` times in `self` and
+    /// `n` times in `other` is yielded `max(m, n)` times. Values are compared
+    /// with the comparator of `self`.
+    pub fn union<'a>(&'a self, other: &'a Self) -> Union<'a, T, C> {
+        Union(MergeIter::new(self, other))
+    }
+
+    /// Iterate in ascending order over the values in both `self` and
+    /// `other`, `min(m, n)` times each.
+    pub fn intersection<'a>(
+        &'a self,
+        other: &'a Self,
+    ) -> Intersection<'a, T, C> {
+        Intersection(MergeIter::new(self, other))
+    }
+
+    /// Iterate in ascending order over the values in `self` but not in
+    /// `other`, `m - n` times each.
+    pub fn difference<'a>(&'a self, other: &'a Self) -> Difference<'a, T, C> {
+        Difference(MergeIter::new(self, other))
+    }
+
+    /// Iterate in ascending order over the values in exactly one of `self`
+    /// and `other`, `|m - n|` times each.
+    pub fn symmetric_difference<'a>(
+        &'a self,
+        other: &'a Self,
+    ) -> SymmetricDifference<'a, T, C> {
+        SymmetricDifference(MergeIter::new(self, other))
+    }
+
+    /// Return whether every value in `self` is also in `other`.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.len() <= other.len() && self.difference(other).next().is_none()
+    }
+
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    /// Return whether `self` and `other` have no value in common.
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        self.intersection(other)
+            .next()
+            .is_none()
+    }
+
+    /// Build a balanced tree out of `values` in O(n) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `values` is not sorted in ascending order.
+    pub fn from_sorted_iter<I>(values: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        C: Default,
+    {
+        Self::from_sorted(values, C::default())
+    }
+
+    fn from_sorted<I>(values: I, compare: C) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let mut runs: Vec<(T, usize)> = Vec::new();
+
+        for value in values {
+            let last = runs
+                .last_mut()
+                .map(|(last, count)| (compare.compare(last, &value), count));
+
+            match last {
+                Some((Ordering::Greater, _)) => {
+                    panic!("values must be sorted in ascending order")
+                },
+                Some((Ordering::Equal, count))
+                    if P::ON_DUPLICATE != OnDuplicate::Keep =>
+                {
+                    if P::ON_DUPLICATE == OnDuplicate::Count {
+                        *count += 1;
+                    }
+                },
+                _ => runs.push((value, 1)),
+            }
+        }
+
+        let len = runs.len();
+        let mut nodes = runs.into_iter().map(|(value, count)| {
+            Box::new(Node {
+                value: Some(value),
+                left: None,
+                right: None,
+                count,
+                size: count,
+            })
+        });
+
+        BinarySearchTree {
+            root: Node::from_link(Node::build(len, &mut nodes)),
+            policy: PhantomData,
+            compare,
+        }
+    }
+
+    /// Restructure the tree into a balanced shape in O(n) time, relinking
+    /// its nodes in place.
+    pub fn rebalance(&mut self) {
+        self.relink(|_| true);
+    }
+
+    /// Detach every node of the tree and link those for which `keep`
+    /// returns `true` back into a balanced shape. `keep` is called on the
+    /// nodes in order, and may update their counts.
+    fn relink<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(&mut Node<T>) -> bool,
+    {
+        let mut link =
+            Node::into_link(mem::replace(&mut self.root, Node::new()));
+        let mut stack = Vec::new();
+        let mut nodes = Vec::new();
+
+        loop {
+            while let Some(mut node) = link {
+                link = node.left.take();
+                stack.push(node);
+            }
+
+            let Some(mut node) = stack.pop() else {
+                break;
+            };
+
+            link = node.right.take();
+
+            if keep(&mut node) {
+                nodes.push(node);
+            }
+        }
+
+        let len = nodes.len();
+
+        self.root = Node::from_link(Node::build(len, &mut nodes.into_iter()));
+    }
+
+    /// Iterate mutably over the values of the tree in ascending order.
+    ///
+    /// Each value is handed out in a [`ValueMut`] guard, through which
+    /// callers may change the parts of the value that the comparator
+    /// ignores. A counted value is yielded once, whatever its number of
+    /// occurrences.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T, C>
+    where
+        T: Clone,
+    {
+        IterMut {
+            walk: Self::walk_mut(&mut self.root),
+            compare: &self.compare,
+        }
+    }
+
+    /// Iterate mutably over the values of the tree in ascending order with
+    /// no guard on their order, for types like
+    /// [`BstMap`](crate::bst_map::BstMap) that only hand out the parts of a
+    /// value the comparator ignores.
+    pub(crate) fn iter_mut_unguarded(
+        &mut self,
+    ) -> impl Iterator<Item = &mut T> + '_ {
+        let mut walk = Self::walk_mut(&mut self.root);
+
+        iter::from_fn(move || walk.next(Expand::expand))
+    }
+
+    fn walk_mut(root: &mut Node<T>) -> InOrder<&mut Node<T>> {
+        InOrder::new(root, |_| {
+            unreachable!("nodes are expanded with a single occurrence")
+        })
+    }
+}
+
+impl<T> Node<T> {
+    fn label(&self) -> String
+    where
+        T: Display,
+    {
+        let value = self.value.as_ref().unwrap();
+
+        match self.count {
+            1 => value.to_string(),
+            count => format!("{value} (x{count})"),
+        }
+    }
+
+    fn new() -> Self {
+        Node {
+            value: None,
+            left: None,
+            right: None,
+            count: 0,
+            size: 0,
+        }
+    }
+
+    fn search_by<F>(&self, mut f: F) -> Option<&T>
+    where
+        F: FnMut(&T) -> Ordering,
+    {
+        let mut node = self;
+
+        loop {
+            let key = node.value.as_ref()?;
+
+            node = match f(key) {
+                Ordering::Equal => return Some(key),
+                Ordering::Greater => node.left.as_deref()?,
+                Ordering::Less => node.right.as_deref()?,
+            };
+        }
+    }
+
+    fn search_by_mut<F>(&mut self, mut f: F) -> Option<&mut T>
+    where
+        F: FnMut(&T) -> Ordering,
+    {
+        let mut node = self;
+
+        loop {
+            node = match f(node.value.as_ref()?) {
+                Ordering::Equal => return node.value.as_mut(),
+                Ordering::Greater => node.left.as_deref_mut()?,
+                Ordering::Less => node.right.as_deref_mut()?,
+            };
+        }
+    }
+
+    fn insert<P, C>(&mut self, value: T, compare: &C) -> (&mut T, bool)
+    where
+        P: DuplicatePolicy<T>,
+        C: Comparator<T>,
+    {
+        // sizes are updated on the way down, so a duplicate that is going
+        // to be rejected has to be found first
+        if P::ON_DUPLICATE == OnDuplicate::Reject
+            && self
+                .search_by(|key| compare.compare(key, &value))
+                .is_some()
+        {
+            let key = self.search_by_mut(|key| compare.compare(key, &value));
+
+            return (key.unwrap(), false);
+        }
+
+        let mut node = self;
+
+        loop {
+            let ordering = match node.value {
+                Some(ref key) => compare.compare(key, &value),
+                None => {
+                    node.count = 1;
+                    node.size = 1;
+
+                    return (node.value.insert(value), true);
+                },
+            };
+
+            node.size += 1;
+
+            let target_node = match (ordering, P::ON_DUPLICATE) {
+                (Ordering::Equal, OnDuplicate::Count) => {
+                    node.count += 1;
+
+                    return (node.value.as_mut().unwrap(), true);
+                },
+                (Ordering::Greater, _) => &mut node.left,
+                _ => &mut node.right,
+            };
+
+            node = target_node.get_or_insert_with(|| Box::new(Node::new()));
+        }
+    }
+
+    fn remove_by<P, F>(&mut self, f: &mut F) -> Option<T>
+    where
+        P: DuplicatePolicy<T>,
+        F: FnMut(&T) -> Ordering,
+    {
+        self.search_by(&mut *f)?;
+
+        let step = |node: &Self| f(node.value.as_ref().unwrap());
+
+        Some(self.remove_along(1, step, Self::remove_one::<P>))
+    }
+
+    fn remove_min<P>(&mut self) -> Option<T>
+    where
+        P: DuplicatePolicy<T>,
+    {
+        self.value.as_ref()?;
+
+        Some(self.remove_along(1, Self::step_min, Self::remove_one::<P>))
+    }
+
+    fn remove_max<P>(&mut self) -> Option<T>
+    where
+        P: DuplicatePolicy<T>,
+    {
+        self.value.as_ref()?;
+
+        Some(self.remove_along(1, Self::step_max, Self::remove_one::<P>))
+    }
+
+    /// Walk down to the node at which `step` returns `Ordering::Equal`, and
+    /// apply `remove` to it, which must remove `removed` values. Sizes are
+    /// updated on the way down, so the node must exist. A child left empty
+    /// is unlinked.
+    fn remove_along<S, F, R>(
+        &mut self,
+        removed: usize,
+        mut step: S,
+        remove: F,
+    ) -> R
+    where
+        S: FnMut(&Self) -> Ordering,
+        F: FnOnce(&mut Self) -> R,
+    {
+        let mut ordering = step(self);
+
+        if ordering == Ordering::Equal {
+            return remove(self);
+        }
+
+        let mut node = self;
+
+        loop {
+            node.size -= removed;
+
+            let target_node = match ordering {
+                Ordering::Greater => &mut node.left,
+                _ => &mut node.right,
+            };
+
+            ordering = step(target_node.as_deref().unwrap());
+
+            if ordering == Ordering::Equal {
+                let child = target_node.as_deref_mut().unwrap();
+                let value = remove(child);
+
+                if child.value.is_none() {
+                    *target_node = None;
+                }
+
+                return value;
+            }
+
+            node = target_node.as_deref_mut().unwrap();
+        }
+    }
+
+    fn step_min(&self) -> Ordering {
+        match self.left {
+            Some(_) => Ordering::Greater,
+            None => Ordering::Equal,
+        }
+    }
+
+    fn step_max(&self) -> Ordering {
+        match self.right {
+            Some(_) => Ordering::Less,
+            None => Ordering::Equal,
+        }
+    }
+
+    /// Remove one occurrence of the value stored in this node.
+    fn remove_one<P>(&mut self) -> T
+    where
+        P: DuplicatePolicy<T>,
+    {
+        if self.count > 1 {
+            self.count -= 1;
+            self.size -= 1;
+
+            P::copy(self.value.as_ref().unwrap())
+        } else {
+            self.remove_here()
+        }
+    }
+
+    /// Remove the value stored in this node, with all its occurrences, and
+    /// splice the tree back together. When the node has two children its
+    /// value is replaced by the in-order successor; otherwise the single
+    /// child (if any) is moved up into this node. A node left without a
+    /// value is empty, which the parent is responsible for unlinking.
+    fn remove_here(&mut self) -> T {
+        match (self.left.take(), self.right.take()) {
+            (None, None) => {
+                self.count = 0;
+                self.size = 0;
+                self.value.take().unwrap()
+            },
+            (Some(node), None) | (None, Some(node)) => {
+                let mut old = mem::replace(self, *node);
+
+                old.value.take().unwrap()
+            },
+            (Some(left), Some(mut right)) => {
+                let (successor, count) = right.take_min().unwrap();
+
+                self.left = Some(left);
+                self.count = count;
+
+                if right.value.is_some() {
+                    self.right = Some(right);
+                }
+
+                self.update_size();
+
+                self.value.replace(successor).unwrap()
+            },
+        }
+    }
+
+    /// Remove the leftmost node of this subtree, returning its value and
+    /// number of occurrences.
+    fn take_min(&mut self) -> Option<(T, usize)> {
+        let mut min = &*self;
+
+        while let Some(left) = min.left.as_deref() {
+            min = left;
+        }
+
+        min.value.as_ref()?;
+
+        let count = min.count;
+
+        Some(self.remove_along(count, Self::step_min, |node| {
+            (node.remove_here(), count)
+        }))
+    }
+
+    /// Remove the rightmost node of this subtree, returning its value and
+    /// number of occurrences.
+    fn take_max(&mut self) -> Option<(T, usize)> {
+        let mut max = &*self;
+
+        while let Some(right) = max.right.as_deref() {
+            max = right;
+        }
+
+        max.value.as_ref()?;
+
+        let count = max.count;
+
+        Some(self.remove_along(count, Self::step_max, |node| {
+            (node.remove_here(), count)
+        }))
+    }
+
+    /// Split the subtree into the values for which `f` returns `true`, which
+    /// must hold for a prefix of the values in ascending order, and the
+    /// others. The nodes along the search path for the boundary are cut
+    /// off, then linked back into the two halves from the bottom up.
+    fn split<F>(mut link: Link<T>, mut f: F) -> (Link<T>, Link<T>)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let mut lesser = Vec::new();
+        let mut greater = Vec::new();
+
+        while let Some(mut node) = link {
+            if f(node.value.as_ref().unwrap()) {
+                link = node.right.take();
+                lesser.push(node);
+            } else {
+                link = node.left.take();
+                greater.push(node);
+            }
+        }
+
+        let less = lesser
+            .into_iter()
+            .rev()
+            .fold(None, |right, mut node| {
+                node.right = right;
+                node.update_size();
+
+                Some(node)
+            });
+
+        let rest = greater
+            .into_iter()
+            .rev()
+            .fold(None, |left, mut node| {
+                node.left = left;
+                node.update_size();
+
+                Some(node)
+            });
+
+        (less, rest)
+    }
+
+    /// Join two subtrees, where no value of `left` is greater than any
+    /// value of `right`, below a new root: the largest value of `left`.
+    fn join(left: Link<T>, right: Link<T>) -> Link<T> {
+        let Some(mut left) = left else {
+            return right;
+        };
+
+        let (pivot, count) = left.take_max().unwrap();
+
+        let mut root = Node {
+            value: Some(pivot),
+            left: Node::into_link(*left),
+            right,
+            count,
+            size: 0,
+        };
+
+        root.update_size();
+
+        Some(Box::new(root))
+    }
+
+    /// Turn a root node into a link, which is empty for an empty tree.
+    fn into_link(node: Self) -> Link<T> {
+        node.value
+            .is_some()
+            .then(|| Box::new(node))
+    }
+
+    /// Turn a link into a root node, which is empty for an empty link.
+    fn from_link(link: Link<T>) -> Self {
+        link.map_or_else(Node::new, |node| *node)
+    }
+
+    fn update_size(&mut self) {
+        self.size = size(&self.left) + size(&self.right) + self.count;
+    }
+
+    /// Link the next `len` nodes of `nodes`, which yields detached nodes in
+    /// ascending order, into a balanced subtree. Equal values may end up on
+    /// either side of each other, which the tree allows as long as no value
+    /// is greater than any value to its right.
+    fn build<I>(len: usize, nodes: &mut I) -> Link<T>
+    where
+        I: Iterator<Item = Box<Node<T>>>,
+    {
+        if len == 0 {
+            return None;
+        }
+
+        let left = Self::build(len / 2, nodes);
+        let mut node = nodes.next()?;
+
+        node.left = left;
+        node.right = Self::build(len - len / 2 - 1, nodes);
+        node.update_size();
+
+        Some(node)
+    }
+}
+
+// Cloning and dropping walk the tree with an explicit stack, as the derived
+// implementations would recurse once per level of a possibly very deep tree.
+
+impl<T> Clone for Node<T>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        let copy = |node: &Self| Node {
+            value: node.value.clone(),
+            left: None,
+            right: None,
+            count: node.count,
+            size: node.size,
+        };
+
+        let mut root = copy(self);
+        let mut stack = vec![(self, &mut root)];
+
+        while let Some((node, clone)) = stack.pop() {
+            if let Some(left) = node.left.as_deref() {
+                stack.push((left, clone.left.insert(Box::new(copy(left)))));
+            }
+
+            if let Some(right) = node.right.as_deref() {
+                stack.push((
+                    right,
+                    clone
+                        .right
+                        .insert(Box::new(copy(right))),
+                ));
+            }
+        }
+
+        root
+    }
+}
+
+impl<T> Drop for Node<T> {
+    fn drop(&mut self) {
+        let children = [self.left.take(), self.right.take()];
+
+        binary_tree::drop_iteratively(
+            children.into_iter().flatten(),
+            |mut node| [node.left.take(), node.right.take()],
+        );
+    }
+}
+
+/// Only nodes below the root of a tree are handed to the shared helpers,
+/// and every one of them holds a value.
+impl<T> BinaryNode for Node<T> {
+    type Value = T;
+
+    fn value(&self) -> &T {
+        self.value.as_ref().unwrap()
+    }
+
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+}
+
+impl<T, P, C> FromIterator<T> for BinarySearchTree<T, P, C>
+where
+    P: DuplicatePolicy<T>,
+    C: Comparator<T> + Default,
+{
+    /// Build a balanced tree, in O(n) time if the values are already sorted
+    /// and O(n log n) otherwise.
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let compare = C::default();
+        let mut values = iter.into_iter().collect::<Vec<_>>();
+
+        if !values.is_sorted_by(|a, b| compare.compare(a, b).is_le()) {
+            values.sort_by(|a, b| compare.compare(a, b));
+        }
+
+        Self::from_sorted(values, compare)
+    }
+}
+
+/// Insert the values one by one, in order, so that the shape of the tree
+/// follows the order of the vector. Use `collect` for a balanced tree.
+impl<T> From<Vec<T>> for BinarySearchTree<T>
+where
+    T: Ord,
+{
+    fn from(value: Vec<T>) -> Self {
+        let mut tree = Self::new();
+
+        for v in value {
+            tree.insert(v);
+        }
+
+        tree
+    }
+}
+
+/// Draw the tree sideways, one value per line, with the root on the left
+/// and greater values above smaller ones.
+impl<T, P, C> Display for BinarySearchTree<T, P, C>
+where
+    T: Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        enum Side {
+            Root,
+            Left,
+            Right,
+        }
+
+        enum Draw<'a, T> {
+            Node(&'a Node<T>, String, Side),
+            Line(String),
+        }
+
+        let mut stack: Vec<_> = self
+            .root
+            .value
+            .as_ref()
+            .map(|_| Draw::Node(&self.root, String::new(), Side::Root))
+            .into_iter()
+            .collect();
+
+        // the right subtree is drawn first, then the node, then the left
+        // subtree, so they are pushed in the opposite order
+        while let Some(draw) = stack.pop() {
+            let (node, prefix, side) = match draw {
+                Draw::Node(node, prefix, side) => (node, prefix, side),
+                Draw::Line(line) => {
+                    writeln!(f, "{line}")?;
+                    continue;
+                },
+            };
+
+            // a child on the far side of its parent needs a line passing
+            // by it to reach the parent
+            let (above, connector, below) = match side {
+                Side::Root => ("", "", ""),
+                Side::Left => ("|   ", "`-- ", "    "),
+                Side::Right => ("    ", ".-- ", "|   "),
+            };
+
+            if let Some(left) = node.left.as_deref() {
+                stack.push(Draw::Node(
+                    left,
+                    prefix.clone() + below,
+                    Side::Left,
+                ));
+            }
+
+            stack.push(Draw::Line(prefix.clone() + connector + &node.label()));
+
+            if let Some(right) = node.right.as_deref() {
+                stack.push(Draw::Node(right, prefix + above, Side::Right));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn size<T>(node: &Link<T>) -> usize {
+    node.as_ref()
+        .map_or(0, |node| node.size)
+}
+
+/// Return whether `key` lies after the start and before the end of
+/// `range`. Since the tree keeps smaller values to the left, the left
+/// subtree of a node can only hold values within the range if its key lies
+/// after the start, and the right subtree only if its key lies before the
+/// end.
+fn position_in<T, Q, R, C>(range: &R, key: &T, compare: &C) -> (bool, bool)
+where
+    T: Borrow<Q>,
+    Q: ?Sized,
+    R: RangeBounds<Q>,
+    C: Comparator<Q>,
+{
+    let key = key.borrow();
+
+    let after_start = match range.start_bound() {
+        Bound::Included(start) => compare.compare(key, start).is_ge(),
+        Bound::Excluded(start) => compare.compare(key, start).is_gt(),
+        Bound::Unbounded => true,
+    };
+
+    let before_end = match range.end_bound() {
+        Bound::Included(end) => compare.compare(key, end).is_le(),
+        Bound::Excluded(end) => compare.compare(key, end).is_lt(),
+        Bound::Unbounded => true,
+    };
+
+    (after_start, before_end)
+}
+
+impl<T, P, C> BitOr for &BinarySearchTree<T, P, C>
+where
+    T: Clone,
+    P: DuplicatePolicy<T>,
+    C: Comparator<T> + Clone,
+{
+    type Output = BinarySearchTree<T, P, C>;
+
+    /// Return the union of `self` and `rhs` as a new, balanced tree.
+    fn bitor(self, rhs: Self) -> Self::Output {
+        BinarySearchTree::from_sorted(
+            self.union(rhs).cloned(),
+            self.compare.clone(),
+        )
+    }
+}
+
+impl<T, P, C> BitAnd for &BinarySearchTree<T, P, C>
+where
+    T: Clone,
+    P: DuplicatePolicy<T>,
+    C: Comparator<T> + Clone,
+{
+    type Output = BinarySearchTree<T, P, C>;
+
+    /// Return the intersection of `self` and `rhs` as a new, balanced tree.
+    fn bitand(self, rhs: Self) -> Self::Output {
+        BinarySearchTree::from_sorted(
+            self.intersection(rhs).cloned(),
+            self.compare.clone(),
+        )
+    }
+}
+
+impl<T, P, C> Sub for &BinarySearchTree<T, P, C>
+where
+    T: Clone,
+    P: DuplicatePolicy<T>,
+    C: Comparator<T> + Clone,
+{
+    type Output = BinarySearchTree<T, P, C>;
+
+    /// Return the difference of `self` and `rhs` as a new, balanced tree.
+    fn sub(self, rhs: Self) -> Self::Output {
+        BinarySearchTree::from_sorted(
+            self.difference(rhs).cloned(),
+            self.compare.clone(),
+        )
+    }
+}
+
+impl<T, P, C> BitXor for &BinarySearchTree<T, P, C>
+where
+    T: Clone,
+    P: DuplicatePolicy<T>,
+    C: Comparator<T> + Clone,
+{
+    type Output = BinarySearchTree<T, P, C>;
+
+    /// Return the symmetric difference of `self` and `rhs` as a new,
+    /// balanced tree.
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        BinarySearchTree::from_sorted(
+            self.symmetric_difference(rhs).cloned(),
+            self.compare.clone(),
+        )
+    }
+}
+
+impl<'a, T, P, C> IntoIterator for &'a BinarySearchTree<T, P, C>
+where
+    P: DuplicatePolicy<T>,
+    C: Comparator<T>,
+{
+    type IntoIter = Iter<'a, T>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T, P, C> IntoIterator for &'a mut BinarySearchTree<T, P, C>
+where
+    T: Clone,
+    P: DuplicatePolicy<T>,
+    C: Comparator<T>,
+{
+    type IntoIter = IterMut<'a, T, C>;
+    type Item = ValueMut<'a, T, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<T, P, C> IntoIterator for BinarySearchTree<T, P, C>
+where
+    P: DuplicatePolicy<T>,
+    C: Comparator<T>,
+{
+    type IntoIter = IntoIter<T>;
+    type Item = T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter::new::<P>(self.root)
+    }
+}
+
+/// A handle to a node that can be split into its left subtree, its value
+/// with its number of occurrences and its right subtree, borrowed or owned.
+trait Expand: Sized {
+    type Value;
+
+    fn expand(self) -> Expanded<Self>;
+}
+
+type Expanded<N> =
+    (Option<N>, Option<(<N as Expand>::Value, usize)>, Option<N>);
+
+impl<'a, T> Expand for &'a Node<T> {
+    type Value = &'a T;
+
+    fn expand(self) -> Expanded<Self> {
+        (
+            self.left.as_deref(),
+            self.value
+                .as_ref()
+                .map(|value| (value, self.count)),
+            self.right.as_deref(),
+        )
+    }
+}
+
+impl<'a, T> Expand for &'a mut Node<T> {
+    type Value = &'a mut T;
+
+    fn expand(self) -> Expanded<Self> {
+        // a mutable reference cannot be handed out twice, so counted
+        // values are yielded once
+        (
+            self.left.as_deref_mut(),
+            self.value
+                .as_mut()
+                .map(|value| (value, 1)),
+            self.right.as_deref_mut(),
+        )
+    }
+}
+
+impl<T> Expand for Box<Node<T>> {
+    type Value = T;
+
+    fn expand(mut self) -> Expanded<Self> {
+        (
+            self.left.take(),
+            self.value
+                .take()
+                .map(|value| (value, self.count)),
+            self.right.take(),
+        )
+    }
+}
+
+enum Step<N, V> {
+    Node(N),
+    Value(V, usize),
+}
+
+/// A lazy in-order walk that can be consumed from both ends.
+///
+/// The deque holds the parts of the tree that have not been yielded yet,
+/// in order. Each end unfolds nodes only until it reaches a value, so the
+/// deque holds at most a couple of entries per level of the tree. The
+/// `expand` step may drop parts of a node to prune the walk, and `copy`
+/// produces the repeated occurrences of a counted value. This is why the
+/// tree does not use the walk in [`binary_tree`], which only goes forwards
+/// over borrowed nodes and yields every node once.
+struct InOrder<N>
+where
+    N: Expand,
+{
+    pending: VecDeque<Step<N, N::Value>>,
+    copy: fn(&N::Value) -> N::Value,
+}
+
+impl<N> InOrder<N>
+where
+    N: Expand,
+{
+    fn new(root: N, copy: fn(&N::Value) -> N::Value) -> Self {
+        InOrder {
+            pending: VecDeque::from([Step::Node(root)]),
+            copy,
+        }
+    }
+
+    fn next<E>(&mut self, mut expand: E) -> Option<N::Value>
+    where
+        E: FnMut(N) -> Expanded<N>,
+    {
+        loop {
+            match self.pending.pop_front()? {
+                Step::Value(value, 1) => return Some(value),
+                Step::Value(value, count) => {
+                    let copy = (self.copy)(&value);
+
+                    self.pending
+                        .push_front(Step::Value(value, count - 1));
+
+                    return Some(copy);
+                },
+                Step::Node(node) => {
+                    let (left, value, right) = expand(node);
+
+                    if let Some(right) = right {
+                        self.pending
+                            .push_front(Step::Node(right));
+                    }
+
+                    if let Some((value, count)) = value {
+                        self.pending
+                            .push_front(Step::Value(value, count));
+                    }
+
+                    if let Some(left) = left {
+                        self.pending
+                            .push_front(Step::Node(left));
+                    }
+                },
+            }
+        }
+    }
+
+    fn next_back<E>(&mut self, mut expand: E) -> Option<N::Value>
+    where
+        E: FnMut(N) -> Expanded<N>,
+    {
+        loop {
+            match self.pending.pop_back()? {
+                Step::Value(value, 1) => return Some(value),
+                Step::Value(value, count) => {
+                    let copy = (self.copy)(&value);
+
+                    self.pending
+                        .push_back(Step::Value(value, count - 1));
+
+                    return Some(copy);
+                },
+                Step::Node(node) => {
+                    let (left, value, right) = expand(node);
+
+                    if let Some(left) = left {
+                        self.pending.push_back(Step::Node(left));
+                    }
+
+                    if let Some((value, count)) = value {
+                        self.pending
+                            .push_back(Step::Value(value, count));
+                    }
+
+                    if let Some(right) = right {
+                        self.pending
+                            .push_back(Step::Node(right));
+                    }
+                },
+            }
+        }
+    }
+}
+
+pub struct Iter<'a, T> {
+    walk: InOrder<&'a Node<T>>,
+    len: usize,
+}
+
+pub struct IterMut<'a, T, C = NaturalOrder> {
+    walk: InOrder<&'a mut Node<T>>,
+    compare: &'a C,
+}
+
+/// A value of a tree borrowed mutably from [`IterMut`].
+///
+/// The first mutable access keeps a copy of the value. When the guard is
+/// dropped, a value that no longer compares equal to that copy would be
+/// out of place in the tree, so the copy is put back and the guard panics.
+pub struct ValueMut<'a, T, C>
+where
+    C: Comparator<T>,
+{
+    value: &'a mut T,
+    original: Option<T>,
+    compare: &'a C,
+}
+
+pub struct IntoIter<T> {
+    walk: InOrder<Box<Node<T>>>,
+    len: usize,
+}
+
+pub struct PreOrder<'a, T> {
+    stack: Vec<&'a Node<T>>,
+    current: Occurrences<&'a T>,
+    len: usize,
+}
+
+pub struct PostOrder<'a, T> {
+    // nodes still to visit, flagged once their subtrees have been queued
+    stack: Vec<(&'a Node<T>, bool)>,
+    current: Occurrences<&'a T>,
+    len: usize,
+}
+
+pub struct LevelOrder<'a, T> {
+    queue: VecDeque<(usize, &'a Node<T>)>,
+    current: Occurrences<(usize, &'a T)>,
+    len: usize,
+}
+
+/// The occurrences of the value of a node that are left to be yielded.
+struct Occurrences<V> {
+    item: Option<V>,
+    left: usize,
+}
+
+impl<V> Default for Occurrences<V> {
+    fn default() -> Self {
+        Occurrences {
+            item: None,
+            left: 0,
+        }
+    }
+}
+
+impl<V> Occurrences<V>
+where
+    V: Copy,
+{
+    fn new(item: V, count: usize) -> Self {
+        Occurrences {
+            item: Some(item),
+            left: count,
+        }
+    }
+
+    fn next(&mut self) -> Option<V> {
+        if self.left == 0 {
+            return None;
+        }
+
+        self.left -= 1;
+
+        self.item
+    }
+}
+
+impl<T> IntoIter<T> {
+    fn new<P>(root: Node<T>) -> Self
+    where
+        P: DuplicatePolicy<T>,
+    {
+        IntoIter {
+            len: root.size,
+            walk: InOrder::new(Box::new(root), P::copy),
+        }
+    }
+}
+
+/// An iterator over the values of a tree within a range of `Q`, a borrowed
+/// form of `T`.
+pub struct Range<'a, T, R, C = NaturalOrder, Q = T>
+where
+    Q: ?Sized,
+{
+    walk: InOrder<&'a Node<T>>,
+    range: R,
+    compare: &'a C,
+    bound: PhantomData<fn(&Q)>,
+}
+
+impl<'a, T, R, C, Q> Range<'a, T, R, C, Q>
+where
+    T: Borrow<Q>,
+    Q: ?Sized,
+    R: RangeBounds<Q>,
+    C: Comparator<Q>,
+{
+    fn expand(
+        range: &R,
+        compare: &C,
+        node: &'a Node<T>,
+    ) -> Expanded<&'a Node<T>> {
+        let (left, value, right) = node.expand();
+
+        let Some((key, _)) = value else {
+            return (left, value, right);
+        };
+
+        let (after_start, before_end) = position_in(range, key, compare);
+
+        (
+            left.filter(|_| after_start),
+            value.filter(|_| after_start && before_end),
+            right.filter(|_| before_end),
+        )
+    }
+}
+
+impl<'a, T, R, C, Q> Iterator for Range<'a, T, R, C, Q>
+where
+    T: Borrow<Q>,
+    Q: ?Sized,
+    R: RangeBounds<Q>,
+    C: Comparator<Q>,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (range, compare) = (&self.range, self.compare);
+
+        self.walk
+            .next(|node| Self::expand(range, compare, node))
+    }
+}
+
+impl<T, R, C, Q> DoubleEndedIterator for Range<'_, T, R, C, Q>
+where
+    T: Borrow<Q>,
+    Q: ?Sized,
+    R: RangeBounds<Q>,
+    C: Comparator<Q>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (range, compare) = (&self.range, self.compare);
+
+        self.walk
+            .next_back(|node| Self::expand(range, compare, node))
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let value = self.walk.next(Expand::expand)?;
+        self.len -= 1;
+
+        Some(value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for Iter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let value = self.walk.next_back(Expand::expand)?;
+        self.len -= 1;
+
+        Some(value)
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<'a, T, C> IterMut<'a, T, C>
+where
+    C: Comparator<T>,
+{
+    fn guard(&self, value: &'a mut T) -> ValueMut<'a, T, C> {
+        ValueMut {
+            value,
+            original: None,
+            compare: self.compare,
+        }
+    }
+}
+
+impl<'a, T, C> Iterator for IterMut<'a, T, C>
+where
+    C: Comparator<T>,
+{
+    type Item = ValueMut<'a, T, C>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let value = self.walk.next(Expand::expand)?;
+
+        Some(self.guard(value))
+    }
+}
+
+impl<T, C> DoubleEndedIterator for IterMut<'_, T, C>
+where
+    C: Comparator<T>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let value = self.walk.next_back(Expand::expand)?;
+
+        Some(self.guard(value))
+    }
+}
+
+impl<T, C> Deref for ValueMut<'_, T, C>
+where
+    C: Comparator<T>,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T, C> DerefMut for ValueMut<'_, T, C>
+where
+    T: Clone,
+    C: Comparator<T>,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        if self.original.is_none() {
+            self.original = Some(self.value.clone());
+        }
+
+        self.value
+    }
+}
+
+impl<T, C> Drop for ValueMut<'_, T, C>
+where
+    C: Comparator<T>,
+{
+    fn drop(&mut self) {
+        let Some(original) = self.original.take() else {
+            return;
+        };
+
+        if self
+            .compare
+            .compare(&original, self.value)
+            .is_ne()
+        {
+            *self.value = original;
+
+            // a second panic while unwinding would abort
+            if !thread::panicking() {
+                panic!("iter_mut moved a value in the order of the tree");
+            }
+        }
+    }
+}
+
+impl<T, C> fmt::Debug for ValueMut<'_, T, C>
+where
+    T: fmt::Debug,
+    C: Comparator<T>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let value = self.walk.next(Expand::expand)?;
+        self.len -= 1;
+
+        Some(value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let value = self.walk.next_back(Expand::expand)?;
+        self.len -= 1;
+
+        Some(value)
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<'a, T> Iterator for PreOrder<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.current.next() {
+                self.len -= 1;
+
+                return Some(value);
+            }
+
+            let node = self.stack.pop()?;
+
+            self.stack.extend(node.right.as_deref());
+            self.stack.extend(node.left.as_deref());
+            self.current =
+                Occurrences::new(node.value.as_ref().unwrap(), node.count);
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for PreOrder<'_, T> {}
+
+impl<'a, T> Iterator for PostOrder<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.current.next() {
+                self.len -= 1;
+
+                return Some(value);
+            }
+
+            let (node, queued) = self.stack.pop()?;
+
+            if queued {
+                self.current =
+                    Occurrences::new(node.value.as_ref().unwrap(), node.count);
+            } else {
+                self.stack.push((node, true));
+                self.stack.extend(
+                    node.right
+                        .as_deref()
+                        .map(|right| (right, false)),
+                );
+                self.stack.extend(
+                    node.left
+                        .as_deref()
+                        .map(|left| (left, false)),
+                );
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for PostOrder<'_, T> {}
+
+impl<'a, T> Iterator for LevelOrder<'a, T> {
+    type Item = (usize, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.current.next() {
+                self.len -= 1;
+
+                return Some(item);
+            }
+
+            let (depth, node) = self.queue.pop_front()?;
+
+            for child in [&node.left, &node.right] {
+                self.queue.extend(
+                    child
+                        .as_deref()
+                        .map(|child| (depth + 1, child)),
+                );
+            }
+
+            self.current = Occurrences::new(
+                (depth, node.value.as_ref().unwrap()),
+                node.count,
+            );
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for LevelOrder<'_, T> {}
+
+// By default a tree is serialized as the sorted sequence of its values,
+// which is compact and independent of its shape, and deserialized into a
+// balanced tree. `Shaped` keeps the exact shape instead.
+
+impl<T, P, C> Serialize for BinarySearchTree<T, P, C>
+where
+    T: Serialize,
+    P: DuplicatePolicy<T>,
+    C: Comparator<T>,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de, T, P, C> Deserialize<'de> for BinarySearchTree<T, P, C>
+where
+    T: Deserialize<'de>,
+    P: DuplicatePolicy<T>,
+    C: Comparator<T> + Default,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let values = Vec::deserialize(deserializer)?;
+
+        Ok(values.into_iter().collect())
+    }
+}
+
+/// Serialize a tree as its nodes in pre-order, preserving its exact shape.
+///
+/// Each node is a map with a `value`, a `count` when the value occurs
+/// other than once, and `has_left` and `has_right` when it has those
+/// children, whose subtrees follow it in the sequence, the left one first.
+/// An empty tree is an empty sequence. As the sequence is flat, trees of any
+/// depth load in formats that limit nesting, such as `serde_json`, and
+/// neither direction recurses. Deserializing checks that the nodes form a
+/// single tree, are ordered and follow the duplicate policy of the tree.
+pub struct Shaped<B>(pub B);
+
+impl<T, P, C> Serialize for Shaped<&BinarySearchTree<T, P, C>>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let root = &self.0.root;
+        let mut stack: Vec<_> = root
+            .value
+            .as_ref()
+            .map(|_| root)
+            .into_iter()
+            .collect();
+
+        serializer.collect_seq(iter::from_fn(move || {
+            let node = stack.pop()?;
+
+            stack.extend(node.right.as_deref());
+            stack.extend(node.left.as_deref());
+
+            Some(NodeRecord {
+                value: node.value.as_ref().unwrap(),
+                count: node.count,
+                has_left: node.left.is_some(),
+                has_right: node.right.is_some(),
+            })
+        }))
+    }
+}
+
+impl<T, P, C> Serialize for Shaped<BinarySearchTree<T, P, C>>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Shaped(&self.0).serialize(serializer)
+    }
+}
+
+impl<'de, T, P, C> Deserialize<'de> for Shaped<BinarySearchTree<T, P, C>>
+where
+    T: Deserialize<'de>,
+    P: DuplicatePolicy<T>,
+    C: Comparator<T> + Default,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let records = Vec::<NodeRecord<T>>::deserialize(deserializer)?;
+        let missing = || D::Error::custom("a node is missing a child");
+        let mut subtrees = Vec::new();
+
+        // read backwards, each node comes right after its subtrees, the
+        // left one last
+        for record in records.into_iter().rev() {
+            let left = match record.has_left {
+                true => Some(subtrees.pop().ok_or_else(missing)?),
+                false => None,
+            };
+            let right = match record.has_right {
+                true => Some(subtrees.pop().ok_or_else(missing)?),
+                false => None,
+            };
+
+            let mut node = Box::new(Node {
+                value: Some(record.value),
+                left,
+                right,
+                count: record.count,
+                size: 0,
+            });
+
+            node.update_size();
+            subtrees.push(node);
+        }
+
+        if subtrees.len() > 1 {
+            return Err(D::Error::custom("the nodes form more than one tree"));
+        }
+
+        let tree = BinarySearchTree {
+            root: Node::from_link(subtrees.pop()),
+            policy: PhantomData,
+            compare: C::default(),
+        };
+
+        tree.check_invariants()
+            .map_err(D::Error::custom)?;
+
+        Ok(Shaped(tree))
+    }
+}
+
+/// A node as laid out by [`Shaped`].
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Node")]
+struct NodeRecord<V> {
+    value: V,
+    #[serde(default = "one", skip_serializing_if = "is_one")]
+    count: usize,
+    #[serde(default, skip_serializing_if = "Not::not")]
+    has_left: bool,
+    #[serde(default, skip_serializing_if = "Not::not")]
+    has_right: bool,
+}
+
+fn one() -> usize {
+    1
+}
+
+fn is_one(count: &usize) -> bool {
+    *count == 1
+}
+
+/// Walks two trees in step, pairing up equal values.
+struct MergeIter<'a, T, C> {
+    a: Peekable<Iter<'a, T>>,
+    b: Peekable<Iter<'a, T>>,
+    compare: &'a C,
+}
+
+impl<'a, T, C> MergeIter<'a, T, C>
+where
+    C: Comparator<T>,
+{
+    fn new<P>(
+        a: &'a BinarySearchTree<T, P, C>,
+        b: &'a BinarySearchTree<T, P, C>,
+    ) -> Self
+    where
+        P: DuplicatePolicy<T>,
+    {
+        MergeIter {
+            a: a.iter().peekable(),
+            b: b.iter().peekable(),
+            compare: &a.compare,
+        }
+    }
+
+    /// Advance past the smallest value left in either tree, returning it
+    /// from each side it was found on.
+    fn next_pair(&mut self) -> (Option<&'a T>, Option<&'a T>) {
+        let ordering = match (self.a.peek(), self.b.peek()) {
+            (Some(a), Some(b)) => self.compare.compare(a, b),
+            _ => Ordering::Equal,
+        };
+
+        match ordering {
+            Ordering::Less => (self.a.next(), None),
+            Ordering::Greater => (None, self.b.next()),
+            Ordering::Equal => (self.a.next(), self.b.next()),
+        }
+    }
+}
+
+pub struct Union<'a, T, C = NaturalOrder>(MergeIter<'a, T, C>);
+
+pub struct Intersection<'a, T, C = NaturalOrder>(MergeIter<'a, T, C>);
+
+pub struct Difference<'a, T, C = NaturalOrder>(MergeIter<'a, T, C>);
+
+pub struct SymmetricDifference<'a, T, C = NaturalOrder>(MergeIter<'a, T, C>);
+
+impl<'a, T, C> Iterator for Union<'a, T, C>
+where
+    C: Comparator<T>,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.0.next_pair() {
+            (Some(a), _) => Some(a),
+            (None, b) => b,
+        }
+    }
+}
+
+impl<'a, T, C> Iterator for Intersection<'a, T, C>
+where
+    C: Comparator<T>,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.0.next_pair() {
+                (Some(a), Some(_)) => return Some(a),
+                (None, None) => return None,
+                _ => {},
+            }
+        }
+    }
+}
+
+impl<'a, T, C> Iterator for Difference<'a, T, C>
+where
+    C: Comparator<T>,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.0.a.peek()?;
+
+            if let (Some(a), None) = self.0.next_pair() {
+                return Some(a);
+            }
+        }
+    }
+}
+
+impl<'a, T, C> Iterator for SymmetricDifference<'a, T, C>
+where
+    C: Comparator<T>,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.0.next_pair() {
+                (Some(a), None) => return Some(a),
+                (None, Some(b)) => return Some(b),
+                (None, None) => return None,
+                (Some(_), Some(_)) => {},
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::panic::{self, AssertUnwindSafe};
+
+    use super::*;
+
+    fn get_data() -> (BinarySearchTree<usize>, Vec<usize>, Vec<usize>) {
+        let vs = vec![5, 1, 4, 4, 4, 6, 5, 4, 5, 6, 5, 9, 7, 6];
+        let copy = vs.clone();
+        let mut sorted = vs.clone();
+        sorted.sort();
+
+        (BinarySearchTree::from(copy), vs, sorted)
+    }
+
+    #[test]
+    fn iter() {
+        let (tree, _, sorted) = get_data();
+        // should iter through the items in ascending order
+        let v = tree
+            .iter()
+            .map(|v| *v)
+            .collect::<Vec<usize>>();
+
+        println!("v = {:?}", v);
+
+        assert_eq!(v, sorted);
+    }
+
+    #[test]
+    fn len() {
+        let (tree, vs, _) = get_data();
+
+        assert_eq!(tree.len(), vs.len());
+    }
+
+    #[test]
+    fn search() {
+        let (tree, vs, _) = get_data();
+
+        for v in &vs {
+            assert_eq!(tree.search(v), true);
+        }
+
+        let falsy = [10, 13, 15, 100];
+
+        for v in &falsy {
+            assert_eq!(tree.search(v), false);
+        }
+    }
+
+    #[test]
+    fn minimum() {
+        let (tree, vs, _) = get_data();
+
+        assert_eq!(tree.minimum(), vs.iter().min());
+    }
+
+    #[test]
+    fn maximum() {
+        let (tree, vs, _) = get_data();
+
+        assert_eq!(tree.maximum(), vs.iter().max());
+    }
+
+    #[test]
+    fn floor() {
+        let (tree, vs, _) = get_data();
+
+        assert_eq!(tree.floor(&100), Some(&9));
+        assert_eq!(tree.floor(&0), None);
+        assert_eq!(tree.floor(&2), Some(&1));
+        assert_eq!(tree.floor(&8), Some(&7));
+
+        for v in &vs {
+            // tree.floor should return the same value if it exists in the tree
+            assert_eq!(tree.floor(v), Some(v));
+        }
+    }
+
+    #[test]
+    fn ceil() {
+        let (tree, vs, _) = get_data();
+
+        assert_eq!(tree.ceil(&100), None);
+        assert_eq!(tree.ceil(&10), None);
+        assert_eq!(tree.ceil(&0), Some(&1));
+        assert_eq!(tree.ceil(&3), Some(&4));
+        assert_eq!(tree.ceil(&8), Some(&9));
+
+        for v in &vs {
+            // tree.ceil should return the same value if it exists in the tree
+            assert_eq!(tree.ceil(v), Some(v));
+        }
+    }
+
+    #[test]
+    fn predecessor_and_successor() {
+        let (tree, vs, sorted) = get_data();
+
+        assert_eq!(tree.predecessor(&1), None);
+        assert_eq!(tree.predecessor(&0), None);
+        assert_eq!(tree.predecessor(&4), Some(&1));
+        assert_eq!(tree.predecessor(&8), Some(&7));
+        assert_eq!(tree.predecessor(&100), Some(&9));
+        assert_eq!(tree.successor(&9), None);
+        assert_eq!(tree.successor(&0), Some(&1));
+        assert_eq!(tree.successor(&4), Some(&5));
+        assert_eq!(tree.successor(&8), Some(&9));
+
+        for v in &vs {
+            assert_eq!(
+                tree.predecessor(v),
+                sorted.iter().rev().find(|s| *s < v)
+            );
+            assert_eq!(tree.successor(v), sorted.iter().find(|s| *s > v));
+        }
+
+        let reversed: BinarySearchTree<_, KeepDuplicates, Descending> =
+            vs.iter().copied().collect();
+
+        assert_eq!(reversed.successor(&5), Some(&4));
+        assert_eq!(reversed.predecessor(&5), Some(&6));
+    }
+
+    #[test]
+    fn nearest() {
+        let tree = BinarySearchTree::from(vec![-10, 0, 4, 7]);
+
+        assert_eq!(tree.nearest(&-100), Some(&-10));
+        assert_eq!(tree.nearest(&-5), Some(&-10));
+        assert_eq!(tree.nearest(&-4), Some(&0));
+        assert_eq!(tree.nearest(&2), Some(&0));
+        assert_eq!(tree.nearest(&3), Some(&4));
+        assert_eq!(tree.nearest(&7), Some(&7));
+        assert_eq!(tree.nearest(&i32::MAX), Some(&7));
+        assert_eq!(BinarySearchTree::<u8>::new().nearest(&1), None);
+    }
+
+    #[test]
+    fn remove() {
+        let (mut tree, vs, mut sorted) = get_data();
+
+        assert_eq!(tree.remove(&100), None);
+        assert_eq!(tree.remove(&0), None);
+
+        for v in &vs {
+            assert_eq!(tree.remove(v), Some(*v));
+
+            let i = sorted.binary_search(v).unwrap();
+            sorted.remove(i);
+
+            assert_eq!(tree.iter().copied().collect::<Vec<_>>(), sorted);
+        }
+
+        // the emptied root must behave like a fresh tree
+        assert_eq!(tree.remove(&5), None);
+        assert_eq!(tree.minimum(), None);
+
+        tree.insert(3);
+        assert_eq!(tree.iter().collect::<Vec<_>>(), vec![&3]);
+    }
+
+    #[test]
+    fn remove_min_and_max() {
+        let (mut tree, _, sorted) = get_data();
+
+        assert_eq!(tree.remove_min(), Some(sorted[0]));
+        assert_eq!(tree.remove_max(), Some(sorted[sorted.len() - 1]));
+        assert_eq!(tree.len(), sorted.len() - 2);
+
+        let mut drained = vec![];
+
+        while let Some(v) = tree.remove_min() {
+            drained.push(v);
+        }
+
+        assert_eq!(drained, sorted[1..sorted.len() - 1]);
+        assert_eq!(tree.remove_max(), None);
+        assert_eq!(tree.len(), 0);
+    }
+
+    #[test]
+    fn retain() {
+        let (mut tree, _, sorted) = get_data();
+        let mut seen = vec![];
+
+        tree.retain(|v| {
+            seen.push(*v);
+            v % 2 == 0
+        });
+
+        // the predicate is called once per value, in order
+        assert_eq!(seen, sorted);
+        assert_eq!(
+            tree.iter().copied().collect::<Vec<_>>(),
+            sorted
+                .iter()
+                .copied()
+                .filter(|v| v % 2 == 0)
+                .collect::<Vec<_>>()
+        );
+
+        tree.retain(|_| false);
+        assert_eq!(tree.len(), 0);
+        assert!(!tree.search(&4));
+    }
+
+    #[test]
+    fn iter_rev() {
+        let (tree, _, mut sorted) = get_data();
+        sorted.reverse();
+
+        assert_eq!(
+            tree.iter()
+                .rev()
+                .copied()
+                .collect::<Vec<_>>(),
+            sorted
+        );
+    }
+
+    #[test]
+    fn iter_both_ends() {
+        let (tree, _, sorted) = get_data();
+        let mut iter = tree.iter();
+        let (mut front, mut back) = (vec![], vec![]);
+
+        // alternate between the two ends until they meet in the middle
+        while let Some(v) = iter.next() {
+            front.push(*v);
+
+            match iter.next_back() {
+                Some(v) => back.push(*v),
+                None => break,
+            }
+        }
+
+        back.reverse();
+        front.append(&mut back);
+
+        assert_eq!(front, sorted);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+    }
+
+    #[test]
+    fn iter_mut() {
+        // ordered by the number alone, so the names may change freely
+        let mut tree = BinarySearchTree::with_comparator(
+            |a: &(usize, String), b: &(usize, String)| a.0.cmp(&b.0),
+        );
+
+        for v in [5, 1, 4, 6, 9] {
+            tree.insert((v, String::new()));
+        }
+
+        for mut v in tree.iter_mut() {
+            let name = v.0.to_string();
+
+            v.1 = name;
+        }
+
+        for mut v in (&mut tree).into_iter().rev().take(2) {
+            v.1.push('!');
+        }
+
+        assert_eq!(
+            tree.iter()
+                .map(|(_, name)| name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["1", "4", "5", "6!", "9!"]
+        );
+        assert!(tree.search(&(9, String::new())));
+    }
+
+    #[test]
+    fn iter_mut_keeps_order() {
+        let (mut tree, _, sorted) = get_data();
+
+        let moved = panic::catch_unwind(AssertUnwindSafe(|| {
+            for mut v in tree.iter_mut().rev() {
+                *v = 0;
+            }
+        }));
+
+        assert!(moved.is_err());
+        assert_eq!(tree.iter().copied().collect::<Vec<_>>(), sorted);
+        assert_eq!(tree.check_invariants(), Ok(()));
+        assert_eq!(tree.floor(&10), Some(&9));
+
+        // reading through the guards changes nothing
+        assert_eq!(
+            tree.iter_mut()
+                .map(|v| *v)
+                .collect::<Vec<_>>(),
+            sorted
+        );
+    }
+
+    #[test]
+    fn into_iter() {
+        let (tree, _, sorted) = get_data();
+
+        assert_eq!(
+            tree.clone()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            sorted
+        );
+
+        let mut iter = tree.into_iter();
+
+        assert_eq!(iter.next_back(), sorted.last().copied());
+        assert_eq!(iter.next(), sorted.first().copied());
+        assert_eq!(iter.count(), sorted.len() - 2);
+
+        let empty = BinarySearchTree::<usize>::new();
+
+        assert_eq!(empty.into_iter().next(), None);
+    }
+
+    #[test]
+    fn range() {
+        let (tree, _, sorted) = get_data();
+
+        let within = |start: Bound<usize>, end: Bound<usize>| {
+            sorted
+                .iter()
+                .copied()
+                .filter(|v| (start, end).contains(v))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            tree.range(4..6)
+                .copied()
+                .collect::<Vec<_>>(),
+            within(Bound::Included(4), Bound::Excluded(6))
+        );
+        assert_eq!(
+            tree.range(4..=6)
+                .copied()
+                .collect::<Vec<_>>(),
+            within(Bound::Included(4), Bound::Included(6))
+        );
+        assert_eq!(
+            tree.range((Bound::Excluded(4), Bound::Unbounded))
+                .copied()
+                .collect::<Vec<_>>(),
+            within(Bound::Excluded(4), Bound::Unbounded)
+        );
+        assert_eq!(
+            tree.range(..5)
+                .rev()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![4, 4, 4, 4, 1]
+        );
+        assert_eq!(tree.range(..).count(), sorted.len());
+        assert_eq!(tree.range(2..4).next(), None);
+        assert_eq!(tree.range(10..).next_back(), None);
+    }
+
+    #[test]
+    fn count_range() {
+        let (tree, _, _) = get_data();
+
+        assert_eq!(tree.count_range(4..=5), 8);
+        assert_eq!(tree.count_range(6..), 5);
+        assert_eq!(tree.count_range(..1), 0);
+        assert_eq!(tree.count_range(..), 14);
+    }
+
+    #[test]
+    fn remove_range() {
+        let (mut tree, _, sorted) = get_data();
+
+        assert_eq!(tree.remove_range(2..4), 0);
+        assert_eq!(tree.remove_range(4..=5), 8);
+        assert_eq!(
+            tree.iter().copied().collect::<Vec<_>>(),
+            sorted
+                .iter()
+                .copied()
+                .filter(|v| !(4..=5).contains(v))
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(tree.remove_range(7..), 2);
+        assert_eq!(tree.iter().copied().collect::<Vec<_>>(), vec![1, 6, 6, 6]);
+
+        assert_eq!(tree.remove_range(..), 4);
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn len_after_updates() {
+        let (mut tree, vs, _) = get_data();
+
+        tree.remove(&4);
+        tree.remove(&100);
+        tree.remove_min();
+        tree.remove_max();
+        assert_eq!(tree.len(), vs.len() - 3);
+
+        tree.retain(|v| *v != 5);
+        assert_eq!(tree.len(), vs.len() - 7);
+
+        tree.remove_range(6..=6);
+        assert_eq!(tree.len(), vs.len() - 10);
+        assert_eq!(tree.iter().len(), tree.len());
+
+        tree.insert(0);
+        assert_eq!(tree.len(), vs.len() - 9);
+        assert_eq!(tree.len(), tree.iter().count());
+    }
+
+    #[test]
+    fn rank() {
+        let (tree, _, sorted) = get_data();
+
+        for v in 0..=10 {
+            let rank = sorted
+                .iter()
+                .filter(|s| **s < v)
+                .count();
+
+            assert_eq!(tree.rank(&v), rank);
+        }
+    }
+
+    #[test]
+    fn select() {
+        let (mut tree, _, mut sorted) = get_data();
+
+        for (k, v) in sorted.iter().enumerate() {
+            assert_eq!(tree.select(k), Some(v));
+        }
+
+        assert_eq!(tree.select(sorted.len()), None);
+
+        tree.remove(&5);
+        sorted.remove(sorted.binary_search(&5).unwrap());
+
+        for (k, v) in sorted.iter().enumerate() {
+            assert_eq!(tree.select(k), Some(v));
+        }
+    }
+
+    #[test]
+    fn median() {
+        let (mut tree, _, sorted) = get_data();
+
+        assert_eq!(tree.median(), Some(&sorted[6]));
+
+        tree.insert(10);
+        assert_eq!(tree.median(), Some(&sorted[7]));
+
+        assert_eq!(BinarySearchTree::<usize>::new().median(), None);
+        assert_eq!(BinarySearchTree::from(vec![3]).median(), Some(&3));
+    }
+
+    fn get_policy_data<P>() -> BinarySearchTree<usize, P>
+    where
+        P: DuplicatePolicy<usize>,
+    {
+        let (_, vs, _) = get_data();
+        let mut tree = BinarySearchTree::default();
+
+        for v in vs {
+            tree.insert(v);
+        }
+
+        tree
+    }
+
+    #[test]
+    fn keep_duplicates() {
+        let (mut tree, vs, _) = get_data();
+
+        assert!(tree.insert(4));
+        assert_eq!(tree.count(&4), 5);
+        assert_eq!(tree.count(&3), 0);
+        assert_eq!(tree.len(), vs.len() + 1);
+    }
+
+    #[test]
+    fn reject_duplicates() {
+        let (_, _, mut sorted) = get_data();
+        let mut tree = get_policy_data::<RejectDuplicates>();
+
+        sorted.dedup();
+
+        assert_eq!(tree.iter().copied().collect::<Vec<_>>(), sorted);
+        assert_eq!(tree.len(), sorted.len());
+        assert_eq!(tree.count(&4), 1);
+
+        assert!(!tree.insert(4));
+        assert!(tree.insert(3));
+        assert_eq!(tree.len(), sorted.len() + 1);
+
+        assert_eq!(tree.remove(&4), Some(4));
+        assert_eq!(tree.remove(&4), None);
+        assert_eq!(tree.count(&4), 0);
+    }
+
+    #[test]
+    fn count_duplicates() {
+        let (_, vs, sorted) = get_data();
+        let mut tree = BinarySearchTree::with_policy(CountDuplicates);
+
+        for v in &vs {
+            assert!(tree.insert(*v));
+        }
+
+        // one node per distinct value, each yielded as often as it was
+        // inserted
+        assert_eq!(tree.len(), vs.len());
+        assert_eq!(tree.iter().copied().collect::<Vec<_>>(), sorted);
+        assert_eq!(tree.iter().rev().count(), vs.len());
+        assert_eq!(tree.count(&4), 4);
+        assert_eq!(tree.count(&5), 4);
+        assert_eq!(tree.count(&9), 1);
+        assert_eq!(tree.rank(&5), 5);
+        assert_eq!(tree.select(8), Some(&5));
+        assert_eq!(tree.count_range(4..=5), 8);
+        assert_eq!(tree.iter_mut().count(), 6);
+
+        assert_eq!(tree.remove(&4), Some(4));
+        assert_eq!(tree.count(&4), 3);
+        assert_eq!(tree.remove_max(), Some(9));
+        assert_eq!(tree.remove_min(), Some(1));
+        assert_eq!(tree.len(), vs.len() - 3);
+
+        assert_eq!(
+            tree.clone()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![4, 4, 4, 5, 5, 5, 5, 6, 6, 6, 7]
+        );
+
+        // the predicate is asked about every occurrence
+        let mut keep = true;
+
+        tree.retain(|_| {
+            keep = !keep;
+            keep
+        });
+
+        assert_eq!(
+            tree.iter().copied().collect::<Vec<_>>(),
+            vec![4, 5, 5, 6, 6]
+        );
+
+        assert_eq!(tree.remove_range(5..), 4);
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree.remove(&4), Some(4));
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn count_duplicates_splice() {
+        let mut tree = get_policy_data::<CountDuplicates>();
+
+        // 5 is the root, with two children; its successor 6 is moved up
+        // together with its counter
+        assert_eq!(tree.count(&5), 4);
+        assert_eq!(tree.remove_range(5..=5), 4);
+        assert_eq!(tree.count(&6), 3);
+        assert_eq!(tree.len(), 10);
+        assert_eq!(tree.select(7), Some(&6));
+        assert_eq!(tree.select(9), Some(&9));
+    }
+
+    fn get_set_data() -> (BinarySearchTree<usize>, BinarySearchTree<usize>) {
+        (
+            BinarySearchTree::from(vec![5, 1, 3, 8, 9, 2]),
+            BinarySearchTree::from(vec![4, 3, 10, 8, 6, 1, 7]),
+        )
+    }
+
+    #[test]
+    fn set_operations() {
+        let (a, b) = get_set_data();
+
+        assert_eq!(
+            a.union(&b).copied().collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10]
+        );
+        assert_eq!(
+            a.intersection(&b)
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![1, 3, 8]
+        );
+        assert_eq!(
+            a.difference(&b)
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![2, 5, 9]
+        );
+        assert_eq!(
+            b.difference(&a)
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![4, 6, 7, 10]
+        );
+        assert_eq!(
+            a.symmetric_difference(&b)
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![2, 4, 5, 6, 7, 9, 10]
+        );
+
+        let empty = BinarySearchTree::new();
+
+        assert_eq!(a.union(&empty).count(), a.len());
+        assert_eq!(a.intersection(&empty).next(), None);
+        assert_eq!(empty.difference(&a).next(), None);
+    }
+
+    #[test]
+    fn set_operations_on_duplicates() {
+        let a = BinarySearchTree::from(vec![1, 1, 1, 2, 3, 3]);
+        let b = BinarySearchTree::from(vec![1, 2, 2, 3, 3, 3]);
+
+        assert_eq!(
+            a.union(&b).copied().collect::<Vec<_>>(),
+            vec![1, 1, 1, 2, 2, 3, 3, 3]
+        );
+        assert_eq!(
+            a.intersection(&b)
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3, 3]
+        );
+        assert_eq!(
+            a.difference(&b)
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![1, 1]
+        );
+        assert_eq!(
+            a.symmetric_difference(&b)
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![1, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn set_operators() {
+        let (a, b) = get_set_data();
+        let values = |tree: BinarySearchTree<usize>| {
+            tree.iter().copied().collect::<Vec<_>>()
+        };
+
+        assert_eq!(values(&a | &b), a.union(&b).copied().collect::<Vec<_>>());
+        assert_eq!(
+            values(&a & &b),
+            a.intersection(&b)
+                .copied()
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(values(&a - &b), vec![2, 5, 9]);
+        assert_eq!(values(&a ^ &b), vec![2, 4, 5, 6, 7, 9, 10]);
+
+        let symmetric_difference = &a ^ &b;
+
+        assert_eq!(symmetric_difference.len(), 7);
+        assert_eq!(symmetric_difference.rank(&6), 3);
+        assert_eq!(symmetric_difference.select(3), Some(&6));
+
+        // the result is balanced: the middle value is at the root
+        assert_eq!((&a | &b).root.value, Some(6));
+    }
+
+    #[test]
+    fn set_operators_follow_policy() {
+        let mut a = BinarySearchTree::with_policy(CountDuplicates);
+        let mut b = BinarySearchTree::with_policy(CountDuplicates);
+
+        for v in [1, 1, 2, 3, 3] {
+            a.insert(v);
+        }
+
+        for v in [1, 3, 3, 3, 4] {
+            b.insert(v);
+        }
+
+        let union = &a | &b;
+
+        assert_eq!(union.count(&1), 2);
+        assert_eq!(union.count(&3), 3);
+        assert_eq!(union.len(), 7);
+        assert_eq!(union.iter().count(), 7);
+        assert_eq!((&a - &b).iter().collect::<Vec<_>>(), vec![&1, &2]);
+    }
+
+    #[test]
+    fn subset_and_disjoint() {
+        let (a, b) = get_set_data();
+        let small = BinarySearchTree::from(vec![8, 1]);
+        let other = BinarySearchTree::from(vec![0, 11, 12]);
+
+        assert!(small.is_subset(&a));
+        assert!(small.is_subset(&b));
+        assert!(a.is_superset(&small));
+        assert!(!a.is_subset(&b));
+        assert!(a.is_subset(&a));
+        assert!(BinarySearchTree::new().is_subset(&a));
+
+        assert!(other.is_disjoint(&a));
+        assert!(!a.is_disjoint(&b));
+        assert!(BinarySearchTree::new().is_disjoint(&a));
+
+        // duplicates count towards inclusion
+        let twice = BinarySearchTree::from(vec![8, 8]);
+
+        assert!(!twice.is_subset(&a));
+    }
+
+    #[test]
+    fn split_off() {
+        let (_, _, sorted) = get_data();
+
+        for key in 0..=10 {
+            let (mut tree, _, _) = get_data();
+            let rest = tree.split_off(&key);
+            let at = sorted.partition_point(|v| *v < key);
+
+            assert_eq!(tree.iter().copied().collect::<Vec<_>>(), sorted[..at]);
+            assert_eq!(rest.iter().copied().collect::<Vec<_>>(), sorted[at..]);
+            assert_eq!(tree.len(), at);
+            assert_eq!(rest.len(), sorted.len() - at);
+            assert_eq!(rest.select(0), sorted.get(at));
+            assert_eq!(
+                tree.median(),
+                sorted[..at].get(at.saturating_sub(1) / 2)
+            );
+        }
+    }
+
+    #[test]
+    fn split_off_counted() {
+        let mut tree = get_policy_data::<CountDuplicates>();
+        let rest = tree.split_off(&5);
+
+        assert_eq!(tree.len(), 5);
+        assert_eq!(tree.count(&4), 4);
+        assert_eq!(rest.len(), 9);
+        assert_eq!(rest.count(&5), 4);
+        assert_eq!(rest.count(&6), 3);
+    }
+
+    #[test]
+    fn append() {
+        let (_, _, sorted) = get_data();
+
+        for key in 0..=10 {
+            let (mut tree, _, _) = get_data();
+            let mut rest = tree.split_off(&key);
+
+            tree.append(&mut rest);
+
+            assert!(rest.is_empty());
+            assert_eq!(tree.len(), sorted.len());
+            assert_eq!(tree.iter().copied().collect::<Vec<_>>(), sorted);
+
+            for (k, v) in sorted.iter().enumerate() {
+                assert_eq!(tree.select(k), Some(v));
+            }
+        }
+    }
+
+    #[test]
+    fn append_unordered() {
+        let (mut a, mut b) = get_set_data();
+        let mut expected = a
+            .iter()
+            .chain(b.iter())
+            .copied()
+            .collect::<Vec<_>>();
+
+        expected.sort();
+        a.append(&mut b);
+
+        assert!(b.is_empty());
+        assert_eq!(a.iter().copied().collect::<Vec<_>>(), expected);
+        assert_eq!(a.len(), expected.len());
+    }
+
+    #[test]
+    fn append_follows_policy() {
+        let mut a = BinarySearchTree::with_policy(RejectDuplicates);
+        let mut b = BinarySearchTree::with_policy(RejectDuplicates);
+
+        a.insert(1);
+        a.insert(2);
+        b.insert(2);
+        b.insert(3);
+
+        a.append(&mut b);
+
+        assert_eq!(a.iter().collect::<Vec<_>>(), vec![&1, &2, &3]);
+        assert_eq!(a.len(), 3);
+    }
+
+    #[test]
+    fn serde_compact() {
+        let (tree, _, sorted) = get_data();
+        let json = serde_json::to_string(&tree).unwrap();
+
+        assert_eq!(json, serde_json::to_string(&sorted).unwrap());
+
+        let copy: BinarySearchTree<usize> =
+            serde_json::from_str(&json).unwrap();
+
+        assert_eq!(
+            copy.iter().collect::<Vec<_>>(),
+            tree.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(copy.len(), tree.len());
+
+        // unsorted input is sorted, and the result is balanced
+        let copy: BinarySearchTree<usize> =
+            serde_json::from_str("[3, 1, 2]").unwrap();
+
+        assert_eq!(
+            serde_json::to_string(&Shaped(&copy)).unwrap(),
+            concat!(
+                r#"[{"value":2,"has_left":true,"has_right":true},"#,
+                r#"{"value":1},{"value":3}]"#
+            )
+        );
+
+        let copy: BinarySearchTree<usize, CountDuplicates> =
+            serde_json::from_str(&json).unwrap();
+
+        assert_eq!(copy.count(&4), 4);
+        assert_eq!(copy.len(), tree.len());
+    }
+
+    #[test]
+    fn serde_shaped() {
+        let (tree, ..) = get_data();
+        let json = serde_json::to_string(&Shaped(&tree)).unwrap();
+        let Shaped(copy): Shaped<BinarySearchTree<usize>> =
+            serde_json::from_str(&json).unwrap();
+
+        assert_eq!(serde_json::to_string(&Shaped(copy)).unwrap(), json);
+        assert_eq!(
+            serde_json::to_string(&Shaped(BinarySearchTree::<usize>::new()))
+                .unwrap(),
+            "[]"
+        );
+
+        let counted = get_policy_data::<CountDuplicates>();
+        let json = serde_json::to_string(&Shaped(&counted)).unwrap();
+        let Shaped(copy): Shaped<BinarySearchTree<usize, CountDuplicates>> =
+            serde_json::from_str(&json).unwrap();
+
+        assert!(json.contains(r#""count":4"#));
+        assert_eq!(copy.len(), counted.len());
+        assert_eq!(copy.count(&4), 4);
+        assert_eq!(copy.rank(&6), counted.rank(&6));
+    }
+
+    #[test]
+    fn serde_shaped_rejects_invalid_trees() {
+        let out_of_order = r#"[{"value":2,"has_left":true},{"value":3}]"#;
+        let duplicate = r#"[{"value":2,"has_right":true},{"value":2}]"#;
+        let counted = r#"[{"value":2,"count":2}]"#;
+        let zero = r#"[{"value":2,"count":0}]"#;
+        let missing_child = r#"[{"value":2,"has_left":true}]"#;
+        let two_trees = r#"[{"value":1},{"value":2}]"#;
+
+        fn load<P>(json: &str) -> bool
+        where
+            P: DuplicatePolicy<usize>,
+        {
+            serde_json::from_str::<Shaped<BinarySearchTree<usize, P>>>(json)
+                .is_ok()
+        }
+
+        assert!(!load::<KeepDuplicates>(out_of_order));
+        assert!(load::<KeepDuplicates>(duplicate));
+        assert!(!load::<RejectDuplicates>(duplicate));
+        assert!(!load::<CountDuplicates>(duplicate));
+        assert!(!load::<KeepDuplicates>(counted));
+        assert!(load::<CountDuplicates>(counted));
+        assert!(!load::<CountDuplicates>(zero));
+        assert!(!load::<KeepDuplicates>(missing_child));
+        assert!(!load::<KeepDuplicates>(two_trees));
+    }
+
+    #[test]
+    fn serde_shaped_deep_tree() {
+        let len = 5_000;
+        let tree = BinarySearchTree::from((0..len).collect::<Vec<_>>());
+        let json = serde_json::to_string(&Shaped(&tree)).unwrap();
+        let Shaped(copy): Shaped<BinarySearchTree<usize>> =
+            serde_json::from_str(&json).unwrap();
+
+        assert_eq!(copy.height(), len);
+        assert_eq!(copy.len(), len);
+        assert!(copy.iter().copied().eq(0..len));
+        assert_eq!(serde_json::to_string(&Shaped(copy)).unwrap(), json);
+    }
+
+    #[test]
+    fn from_sorted_iter() {
+        let tree = BinarySearchTree::<usize>::from_sorted_iter(0..1000);
+
+        assert_eq!(tree.len(), 1000);
+        assert_eq!(tree.height(), 10);
+        assert!(tree.iter().copied().eq(0..1000));
+
+        let (_, _, sorted) = get_data();
+        let counted = BinarySearchTree::<_, CountDuplicates>::from_sorted_iter(
+            sorted.iter().copied(),
+        );
+
+        assert_eq!(counted.len(), sorted.len());
+        assert_eq!(counted.count(&4), 4);
+        assert_eq!(counted.height(), 3);
+
+        let empty = BinarySearchTree::<usize>::from_sorted_iter(None);
+
+        assert!(empty.is_empty());
+        assert_eq!(empty.height(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_sorted_iter_unsorted() {
+        BinarySearchTree::<usize>::from_sorted_iter(vec![1, 3, 2]);
+    }
+
+    #[test]
+    fn collect() {
+        let (_, vs, sorted) = get_data();
+        let tree: BinarySearchTree<usize> = vs.into_iter().collect();
+
+        assert_eq!(tree.iter().copied().collect::<Vec<_>>(), sorted);
+        assert_eq!(tree.height(), 4);
+
+        let tree: BinarySearchTree<usize, RejectDuplicates> =
+            (0..100).rev().collect();
+
+        assert!(tree.iter().copied().eq(0..100));
+        assert_eq!(tree.height(), 7);
+    }
+
+    #[test]
+    fn rebalance() {
+        let mut tree = BinarySearchTree::from((0..1000).collect::<Vec<_>>());
+
+        assert_eq!(tree.height(), 1000);
+
+        tree.rebalance();
+
+        assert_eq!(tree.height(), 10);
+        assert_eq!(tree.len(), 1000);
+        assert!(tree.iter().copied().eq(0..1000));
+
+        for k in [0, 1, 499, 999] {
+            assert_eq!(tree.select(k), Some(&k));
+            assert_eq!(tree.rank(&k), k);
+        }
+
+        let mut counted = get_policy_data::<CountDuplicates>();
+        let len = counted.len();
+
+        counted.rebalance();
+
+        assert_eq!(counted.len(), len);
+        assert_eq!(counted.count(&5), 4);
+        assert_eq!(counted.height(), 3);
+
+        let mut empty = BinarySearchTree::<usize>::new();
+
+        empty.rebalance();
+
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn deep_tree() {
+        let len = 1_000_000;
+
+        let spine = binary_tree::right_spine(0..len, |value, right| {
+            Box::new(Node {
+                value: Some(value),
+                left: None,
+                right,
+                count: 1,
+                size: len - value,
+            })
+        });
+
+        let mut tree = BinarySearchTree::<usize>::new();
+        tree.root = Node::from_link(spine);
+
+        assert!(tree.insert(len));
+        assert_eq!(tree.len(), len + 1);
+        assert!(tree.search(&(len - 1)));
+        assert!(!tree.search(&(len + 1)));
+        assert_eq!(tree.minimum(), Some(&0));
+        assert_eq!(tree.maximum(), Some(&len));
+        assert_eq!(tree.floor(&(len + 5)), Some(&len));
+        assert_eq!(tree.ceil(&(len - 1)), Some(&(len - 1)));
+        assert_eq!(tree.rank(&len), len);
+        assert_eq!(tree.select(len / 2), Some(&(len / 2)));
+        assert_eq!(tree.iter().count(), len + 1);
+        assert_eq!(tree.iter().next_back(), Some(&len));
+        assert_eq!(tree.iter_preorder().last(), Some(&len));
+        assert_eq!(tree.iter_postorder().next(), Some(&len));
+        assert_eq!(tree.iter_level_order().last(), Some((len, &len)));
+        assert_eq!(tree.height(), len + 1);
+        assert!(tree.is_valid_bst());
+
+        assert_eq!(tree.remove(&(len / 2)), Some(len / 2));
+        assert_eq!(tree.remove_max(), Some(len));
+        assert_eq!(tree.remove_min(), Some(0));
+        assert_eq!(tree.remove_range(..10), 9);
+        assert_eq!(tree.len(), len - 11);
+
+        let copy = tree.clone();
+        let mut rest = tree.split_off(&(len - 10));
+
+        assert_eq!(rest.len(), 10);
+
+        tree.append(&mut rest);
+        tree.retain(|v| v % 2 == 0);
+
+        assert_eq!(tree.len(), len / 2 - 6);
+        assert_eq!(copy.len(), len - 11);
+        assert_eq!(copy.into_iter().next_back(), Some(len - 1));
+    }
+
+    #[test]
+    fn comparator() {
+        let mut floats =
+            BinarySearchTree::with_comparator(|a: &f64, b: &f64| {
+                a.total_cmp(b)
+            });
+
+        for v in [2.5, -1.0, 0.5, 8.0, 0.5] {
+            floats.insert(v);
+        }
+
+        assert!(floats.search(&0.5));
+        assert!(!floats.search(&1.0));
+        assert_eq!(floats.floor(&1.0), Some(&0.5));
+        assert_eq!(floats.ceil(&1.0), Some(&2.5));
+        assert_eq!(floats.count(&0.5), 2);
+        assert_eq!(floats.range(0.0..3.0).count(), 3);
+        assert_eq!(
+            floats
+                .iter()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![-1.0, 0.5, 0.5, 2.5, 8.0]
+        );
+
+        let mut words = BinarySearchTree::with_policy_and_comparator(
+            RejectDuplicates,
+            |a: &&str, b: &&str| a.to_lowercase().cmp(&b.to_lowercase()),
+        );
+
+        assert!(words.insert("Banana"));
+        assert!(words.insert("apple"));
+        assert!(!words.insert("BANANA"));
+        assert!(words.search(&"APPLE"));
+        assert_eq!(words.remove(&"banana"), Some("Banana"));
+        assert_eq!(words.len(), 1);
+    }
+
+    #[derive(Debug, Clone, Copy, Default)]
+    struct Descending;
+
+    impl Comparator<usize> for Descending {
+        fn compare(&self, a: &usize, b: &usize) -> Ordering {
+            b.cmp(a)
+        }
+    }
+
+    #[test]
+    fn comparator_reverse() {
+        let (_, vs, mut sorted) = get_data();
+        let mut tree: BinarySearchTree<_, KeepDuplicates, Descending> =
+            vs.iter().copied().collect();
+
+        sorted.reverse();
+
+        assert_eq!(tree.iter().copied().collect::<Vec<_>>(), sorted);
+        assert_eq!(tree.minimum(), Some(&9));
+        assert_eq!(tree.floor(&8), Some(&9));
+        assert_eq!(tree.ceil(&8), Some(&7));
+        assert_eq!(tree.rank(&5), 5);
+        assert_eq!(
+            tree.count_range((Bound::Included(6), Bound::Included(4))),
+            11
+        );
+
+        let rest = tree.split_off(&5);
+
+        assert_eq!(
+            tree.iter().copied().collect::<Vec<_>>(),
+            vec![9, 7, 6, 6, 6]
+        );
+        assert_eq!(rest.minimum(), Some(&5));
+        assert_eq!((&tree | &rest).len(), vs.len());
+
+        let json = serde_json::to_string(&tree).unwrap();
+
+        assert_eq!(json, "[9,7,6,6,6]");
+        assert_eq!(
+            serde_json::from_str::<
+                BinarySearchTree<usize, KeepDuplicates, Descending>,
+            >("[6,9,7]")
+            .unwrap()
+            .iter()
+            .collect::<Vec<_>>(),
+            vec![&9, &7, &6]
+        );
+    }
+
+    #[test]
+    fn borrowed_lookups() {
+        let mut tree: BinarySearchTree<String> =
+            ["pear", "apple", "fig", "kiwi"]
+                .into_iter()
+                .map(String::from)
+                .collect();
+
+        assert!(tree.search("fig"));
+        assert!(!tree.search("plum"));
+        assert_eq!(tree.count("kiwi"), 1);
+        assert_eq!(tree.rank("kiwi"), 2);
+        assert_eq!(tree.floor("grape").map(String::as_str), Some("fig"));
+        assert_eq!(tree.ceil("grape").map(String::as_str), Some("kiwi"));
+
+        let within = (Bound::Included("b"), Bound::Excluded("l"));
+
+        assert_eq!(
+            tree.range::<str, _>(within)
+                .collect::<Vec<_>>(),
+            vec!["fig", "kiwi"]
+        );
+        assert_eq!(tree.count_range::<str, _>(within), 2);
+        assert_eq!(tree.remove("apple"), Some(String::from("apple")));
+        assert_eq!(tree.remove("apple"), None);
+
+        let rest = tree.split_off("g");
+
+        assert_eq!(rest.iter().collect::<Vec<_>>(), vec!["kiwi", "pear"]);
+        assert_eq!(tree.remove_range(..=String::from("fig")), 1);
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn traversals() {
+        let tree = BinarySearchTree::from(vec![5, 3, 8, 1, 4, 9]);
+
+        assert_eq!(
+            tree.iter_preorder()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![5, 3, 1, 4, 8, 9]
+        );
+        assert_eq!(
+            tree.iter_postorder()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![1, 4, 3, 9, 8, 5]
+        );
+        assert_eq!(
+            tree.iter_level_order()
+                .map(|(depth, v)| (depth, *v))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, 5),
+                (1, 3),
+                (1, 8),
+                (2, 1),
+                (2, 4),
+                (2, 9)
+            ]
+        );
+
+        let empty = BinarySearchTree::<usize>::new();
+
+        assert_eq!(empty.iter_preorder().next(), None);
+        assert_eq!(empty.iter_postorder().next(), None);
+        assert_eq!(empty.iter_level_order().next(), None);
+    }
+
+    #[test]
+    fn traversals_counted() {
+        let tree: BinarySearchTree<_, CountDuplicates> =
+            BinarySearchTree::from_sorted_iter([1, 2, 2, 3, 3, 3]);
+
+        let mut level_order = tree.iter_level_order();
+
+        assert_eq!(level_order.len(), 6);
+        assert_eq!(level_order.next(), Some((0, &2)));
+        assert_eq!(level_order.len(), 5);
+        assert_eq!(
+            level_order.collect::<Vec<_>>(),
+            vec![
+                (0, &2),
+                (1, &1),
+                (1, &3),
+                (1, &3),
+                (1, &3)
+            ]
+        );
+        assert_eq!(
+            tree.iter_preorder()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![2, 2, 1, 3, 3, 3]
+        );
+        assert_eq!(
+            tree.iter_postorder()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![1, 3, 3, 3, 2, 2]
+        );
+        assert_eq!(tree.iter_postorder().len(), tree.len());
+    }
+
+    #[test]
+    fn introspection() {
+        let tree = BinarySearchTree::from(vec![5, 3, 8, 1, 4, 9, 10, 11]);
+
+        assert_eq!(tree.height(), 5);
+        assert_eq!(tree.node_count(), 8);
+        assert!(!tree.is_balanced());
+        assert!(tree.is_valid_bst());
+        assert_eq!(
+            tree.stats(),
+            TreeStats {
+                len: 8,
+                node_count: 8,
+                height: 5,
+                depth_histogram: vec![1, 2, 3, 1, 1],
+                balance_factor: -2,
+                max_imbalance: 3,
+            }
+        );
+
+        let mut tree = get_policy_data::<CountDuplicates>();
+
+        assert!(tree.node_count() < tree.len());
+        assert!(tree.is_valid_bst());
+
+        tree.rebalance();
+
+        assert!(tree.is_balanced());
+        assert_eq!(tree.stats().max_imbalance, 1);
+
+        let empty = BinarySearchTree::<usize>::new();
+
+        assert_eq!(empty.height(), 0);
+        assert_eq!(empty.node_count(), 0);
+        assert!(empty.is_balanced());
+        assert!(empty.is_valid_bst());
+        assert!(empty.stats().depth_histogram.is_empty());
+    }
+
+    #[test]
+    fn introspection_after_mixed_operations() {
+        let (mut tree, ..) = get_data();
+
+        tree.remove(&5);
+        tree.insert(2);
+        tree.remove_range(6..8);
+        tree.append(&mut BinarySearchTree::from(vec![3, 12, 0]));
+        tree.retain(|v| v % 3 != 0);
+        tree.insert(4);
+
+        let mut rest = tree.split_off(&5);
+
+        for t in [&tree, &rest] {
+            assert!(t.is_valid_bst());
+            assert_eq!(t.stats().len, t.len());
+            assert_eq!(t.node_count(), t.len());
+        }
+
+        rest.rebalance();
+
+        assert!(rest.is_balanced());
+    }
+
+    #[test]
+    fn introspection_detects_corruption() {
+        let mut tree = BinarySearchTree::from(vec![5, 3, 8]);
+
+        tree.root.left.as_mut().unwrap().value = Some(6);
+
+        assert!(!tree.is_valid_bst());
+
+        let mut tree = BinarySearchTree::from(vec![5, 3, 8]);
+
+        tree.root.size = 4;
+
+        assert!(!tree.is_valid_bst());
+
+        let mut tree: BinarySearchTree<_, RejectDuplicates> =
+            BinarySearchTree::from_sorted_iter([1, 2, 3]);
+
+        tree.root.count = 2;
+        tree.root.size += 1;
+
+        assert!(!tree.is_valid_bst());
+    }
+
+    #[test]
+    fn to_dot() {
+        let tree = BinarySearchTree::from(vec![5, 3, 8, 1, 4, 9]);
+
+        assert_eq!(
+            tree.to_dot(false),
+            "digraph {
+    n0 [label=\"5\"];
+    n0 -> n1;
+    n0 -> n2;
+    n2 [label=\"8\"];
+    n2 -> n3;
+    n3 [label=\"9\"];
+    n1 [label=\"3\"];
+    n1 -> n4;
+    n1 -> n5;
+    n5 [label=\"4\"];
+    n4 [label=\"1\"];
+}
+"
+        );
+
+        let tree: BinarySearchTree<_, CountDuplicates> =
+            BinarySearchTree::from_sorted_iter(["a\"b", "c", "c"]);
+
+        assert_eq!(
+            tree.to_dot(true),
+            "digraph {
+    n0 [label=\"c (x2)\"];
+    n0 -> n1;
+    null2 [shape=point];
+    n0 -> null2;
+    n1 [label=\"a\\\"b\"];
+    null3 [shape=point];
+    n1 -> null3;
+    null4 [shape=point];
+    n1 -> null4;
+}
+"
+        );
+        assert_eq!(
+            BinarySearchTree::<usize>::new().to_dot(true),
+            "digraph {\n}\n"
+        );
+    }
+
+    #[test]
+    fn display() {
+        let tree = BinarySearchTree::from(vec![5, 3, 8, 1, 4, 9]);
+
+        assert_eq!(
+            tree.to_string(),
+            "    .-- 9
+.-- 8
+5
+|   .-- 4
+`-- 3
+    `-- 1
+"
+        );
+
+        let tree: BinarySearchTree<_, CountDuplicates> =
+            BinarySearchTree::from_sorted_iter([1, 2, 2, 3]);
+
+        assert_eq!(tree.to_string(), ".-- 3\n2 (x2)\n`-- 1\n");
+        assert_eq!(BinarySearchTree::<usize>::new().to_string(), "");
+    }
+}
+
+/// Random sequences of operations applied to a tree and to a `BTreeMap` of
+/// value counts in lockstep. A sequence on which they disagree is shrunk to
+/// a minimal one before it is reported, along with the seed that produced
+/// it. Set `DIFFERENTIAL_SEED` to run a single seed.
+#[cfg(test)]
+mod differential {
+    use std::{collections::BTreeMap, env, iter};
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    const SEEDS: u64 = 64;
+    const OPS: usize = 1000;
+    // small enough for values to come up again and again
+    const VALUES: i32 = 64;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Op {
+        Insert(i32),
+        Search(i32),
+        Floor(i32),
+        Ceil(i32),
+        Remove(i32),
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Outcome {
+        Bool(bool),
+        Value(Option<i32>),
+    }
+
+    impl Op {
+        fn random(rng: &mut StdRng) -> Self {
+            let value = rng.gen_range(0..VALUES);
+
+            match rng.gen_range(0..6) {
+                0 | 1 => Op::Insert(value),
+                2 => Op::Search(value),
+                3 => Op::Floor(value),
+                4 => Op::Ceil(value),
+                5 => Op::Remove(value),
+                _ => unreachable!("the range holds one value per arm"),
+            }
+        }
+
+        /// The position of the variant in `Op`, so that a test can check
+        /// that `random` comes up with every one of them.
+        fn kind(self) -> usize {
+            match self {
+                Op::Insert(_) => 0,
+                Op::Search(_) => 1,
+                Op::Floor(_) => 2,
+                Op::Ceil(_) => 3,
+                Op::Remove(_) => 4,
+            }
+        }
+
+        fn value(self) -> i32 {
+            match self {
+                Op::Insert(v)
+                | Op::Search(v)
+                | Op::Floor(v)
+                | Op::Ceil(v)
+                | Op::Remove(v) => v,
+            }
+        }
+
+        fn with_value(self, v: i32) -> Self {
+            match self {
+                Op::Insert(_) => Op::Insert(v),
+                Op::Search(_) => Op::Search(v),
+                Op::Floor(_) => Op::Floor(v),
+                Op::Ceil(_) => Op::Ceil(v),
+                Op::Remove(_) => Op::Remove(v),
+            }
+        }
+
+        fn apply_tree<P>(self, tree: &mut BinarySearchTree<i32, P>) -> Outcome
+        where
+            P: DuplicatePolicy<i32>,
+        {
+            match self {
+                Op::Insert(v) => Outcome::Bool(tree.insert(v)),
+                Op::Search(v) => Outcome::Bool(tree.search(&v)),
+                Op::Floor(v) => Outcome::Value(tree.floor(&v).copied()),
+                Op::Ceil(v) => Outcome::Value(tree.ceil(&v).copied()),
+                Op::Remove(v) => Outcome::Value(tree.remove(&v)),
+            }
+        }
+
+        fn apply_model<P>(self, model: &mut BTreeMap<i32, usize>) -> Outcome
+        where
+            P: DuplicatePolicy<i32>,
+        {
+            match self {
+                Op::Insert(v) => {
+                    let count = model.entry(v).or_default();
+                    let grew =
+                        *count == 0 || P::ON_DUPLICATE != OnDuplicate::Reject;
+
+                    *count += usize::from(grew);
+
+                    Outcome::Bool(grew)
+                },
+                Op::Search(v) => Outcome::Bool(model.contains_key(&v)),
+                Op::Floor(v) => Outcome::Value(
+                    model
+                        .range(..=v)
+                        .next_back()
+                        .map(|(v, _)| *v),
+                ),
+                Op::Ceil(v) => {
+                    Outcome::Value(model.range(v..).next().map(|(v, _)| *v))
+                },
+                Op::Remove(v) => {
+                    let count = model.get_mut(&v);
+                    let removed = count.map(|count| {
+                        *count -= 1;
+                        *count
+                    });
+
+                    if removed == Some(0) {
+                        model.remove(&v);
+                    }
+
+                    Outcome::Value(removed.map(|_| v))
+                },
+            }
+        }
+    }
+
+    /// Apply `ops` to an empty tree and model, describing the first step
+    /// at which they disagree.
+    fn run<P>(ops: &[Op]) -> Result<(), String>
+    where
+        P: DuplicatePolicy<i32>,
+    {
+        let mut tree = BinarySearchTree::<i32, P>::default();
+        let mut model = BTreeMap::new();
+
+        for (i, op) in ops.iter().enumerate() {
+            let expected = op.apply_model::<P>(&mut model);
+            let actual = op.apply_tree(&mut tree);
+
+            if actual != expected {
+                return Err(format!(
+                    "step {i}: {op:?} gave {actual:?}, expected {expected:?}"
+                ));
+            }
+
+            if tree.len() != model.values().sum::<usize>() {
+                return Err(format!("step {i}: {op:?} left a wrong len"));
+            }
+
+            if let Err(error) = tree.check_invariants() {
+                return Err(format!("step {i}: {op:?}: {error}"));
+            }
+        }
+
+        let contents = model
+            .iter()
+            .flat_map(|(v, count)| iter::repeat_n(v, *count));
+
+        if !tree.iter().eq(contents) {
+            return Err(String::from("the tree holds the wrong values"));
+        }
+
+        Ok(())
+    }
+
+    /// Shrink `ops` while `fails` still holds for them: first by dropping
+    /// runs of operations, halving the length of the runs down to single
+    /// operations, then by making the values left smaller.
+    fn shrink<F>(mut ops: Vec<Op>, fails: F) -> Vec<Op>
+    where
+        F: Fn(&[Op]) -> bool,
+    {
+        let mut chunk = ops.len() / 2;
+
+        while chunk > 0 {
+            let mut start = 0;
+
+            while start < ops.len() {
+                let end = ops.len().min(start + chunk);
+                let candidate: Vec<_> = ops[..start]
+                    .iter()
+                    .chain(&ops[end..])
+                    .copied()
+                    .collect();
+
+                if fails(&candidate) {
+                    ops = candidate;
+                } else {
+                    start += chunk;
+                }
+            }
+
+            chunk /= 2;
+        }
+
+        // every occurrence of a value is changed at once, as failures
+        // usually take several operations on the same value
+        let mut values: Vec<_> = ops
+            .iter()
+            .map(|op| op.value())
+            .collect();
+        values.sort();
+        values.dedup();
+
+        for old in values {
+            for new in 0..old {
+                let candidate: Vec<_> = ops
+                    .iter()
+                    .map(|op| {
+                        if op.value() == old {
+                            op.with_value(new)
+                        } else {
+                            *op
+                        }
+                    })
+                    .collect();
+
+                if fails(&candidate) {
+                    ops = candidate;
+                    break;
+                }
+            }
+        }
+
+        ops
+    }
+
+    fn check<P>()
+    where
+        P: DuplicatePolicy<i32>,
+    {
+        let seeds = match env::var("DIFFERENTIAL_SEED") {
+            Ok(seed) => {
+                let seed = seed
+                    .parse()
+                    .expect("DIFFERENTIAL_SEED is a u64");
+
+                seed..seed + 1
+            },
+            Err(_) => 0..SEEDS,
+        };
+
+        for seed in seeds {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let ops: Vec<_> = (0..OPS)
+                .map(|_| Op::random(&mut rng))
+                .collect();
+
+            if run::<P>(&ops).is_ok() {
+                continue;
+            }
+
+            let ops = shrink(ops, |ops| run::<P>(ops).is_err());
+
+            panic!(
+                "seed {seed}: {}\nminimal operations: {ops:?}",
+                run::<P>(&ops).unwrap_err()
+            );
+        }
+    }
+
+    #[test]
+    fn generates_every_op() {
+        let mut counts = [0; 5];
+
+        for seed in 0..SEEDS {
+            let mut rng = StdRng::seed_from_u64(seed);
+
+            for _ in 0..OPS {
+                counts[Op::random(&mut rng).kind()] += 1;
+            }
+        }
+
+        assert!(counts.iter().all(|&count| count > 0), "{counts:?}");
+    }
+
+    #[test]
+    fn keep_duplicates() {
+        check::<KeepDuplicates>();
+    }
+
+    #[test]
+    fn reject_duplicates() {
+        check::<RejectDuplicates>();
+    }
+
+    #[test]
+    fn count_duplicates() {
+        check::<CountDuplicates>();
+    }
+
+    #[test]
+    fn shrink_to_minimal_case() {
+        // fails whenever a 5 is inserted and later removed
+        let fails = |ops: &[Op]| {
+            ops.iter()
+                .position(|op| *op == Op::Insert(5))
+                .is_some_and(|i| ops[i..].contains(&Op::Remove(5)))
+        };
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut ops: Vec<_> = (0..OPS)
+            .map(|_| Op::random(&mut rng))
+            .collect();
+
+        ops.insert(100, Op::Insert(5));
+        ops.insert(900, Op::Remove(5));
+
+        assert_eq!(shrink(ops, fails), vec![Op::Insert(5), Op::Remove(5)]);
+
+        // the failure no longer depends on a 5 in particular
+        let fails = |ops: &[Op]| {
+            ops.iter().enumerate().any(|(i, op)| {
+                matches!(op, Op::Insert(_))
+                    && ops[i..].contains(&Op::Remove(op.value()))
+            })
+        };
+        let ops = vec![
+            Op::Search(2),
+            Op::Insert(9),
+            Op::Floor(1),
+            Op::Remove(9),
+        ];
+
+        assert_eq!(shrink(ops, fails), vec![Op::Insert(0), Op::Remove(0)]);
+
+        // values are made as small as the failure allows
+        let fails = |ops: &[Op]| ops.iter().any(|op| op.value() >= 3);
+
+        assert_eq!(
+            shrink(vec![Op::Search(1), Op::Ceil(60)], fails),
+            vec![Op::Ceil(3)]
+        );
+    }
 }
@@ -0,0 +1,136 @@
+//! Pieces shared by the trees whose nodes own their children through
+//! pointers.
+//!
+//! Those trees can grow as deep as they have nodes, so nothing in here
+//! recurses; dropping a deep tree the default way, one nested `Drop` call
+//! per level, would overflow the stack.
+
+/// A node holding a value and up to two children, the left one with the
+/// lesser values.
+pub(crate) trait BinaryNode {
+    type Value;
+
+    fn value(&self) -> &Self::Value;
+
+    fn left(&self) -> Option<&Self>;
+
+    fn right(&self) -> Option<&Self>;
+}
+
+/// Return the value of the leftmost node below `node`.
+pub(crate) fn minimum<N>(mut node: &N) -> &N::Value
+where
+    N: BinaryNode,
+{
+    while let Some(left) = node.left() {
+        node = left;
+    }
+
+    node.value()
+}
+
+/// Return the value of the rightmost node below `node`.
+pub(crate) fn maximum<N>(mut node: &N) -> &N::Value
+where
+    N: BinaryNode,
+{
+    while let Some(right) = node.right() {
+        node = right;
+    }
+
+    node.value()
+}
+
+/// An in-order walk keeping the nodes whose value is still to come on a
+/// stack, so that it takes memory in proportion to the height of the tree.
+pub(crate) struct InOrder<'a, N> {
+    stack: Vec<&'a N>,
+}
+
+impl<'a, N> InOrder<'a, N>
+where
+    N: BinaryNode,
+{
+    pub(crate) fn new(root: Option<&'a N>) -> Self {
+        Self::pruned(root, |_| true)
+    }
+
+    /// Start a walk that leaves out the subtrees under the nodes on the
+    /// left spine of `root` that `enter` rejects.
+    pub(crate) fn pruned<F>(root: Option<&'a N>, enter: F) -> Self
+    where
+        F: FnMut(&N) -> bool,
+    {
+        let mut walk = InOrder { stack: Vec::new() };
+
+        walk.push_left_spine(root, enter);
+
+        walk
+    }
+
+    /// Return the next node in order, leaving its right subtree for the
+    /// caller to push.
+    pub(crate) fn next_node(&mut self) -> Option<&'a N> {
+        self.stack.pop()
+    }
+
+    /// Push `node` and its left descendants, stopping at the first one
+    /// that `enter` rejects.
+    pub(crate) fn push_left_spine<F>(
+        &mut self,
+        mut node: Option<&'a N>,
+        mut enter: F,
+    ) where
+        F: FnMut(&N) -> bool,
+    {
+        while let Some(n) = node.filter(|n| enter(n)) {
+            self.stack.push(n);
+            node = n.left();
+        }
+    }
+}
+
+impl<'a, N> Iterator for InOrder<'a, N>
+where
+    N: BinaryNode,
+{
+    type Item = &'a N::Value;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.next_node()?;
+
+        self.push_left_spine(node.right(), |_| true);
+
+        Some(node.value())
+    }
+}
+
+/// Drop the trees under `roots` one node at a time. `detach` takes the
+/// children out of a node, which can then be dropped on its own, or returns
+/// none if the node is still in use elsewhere.
+pub(crate) fn drop_iteratively<L, I, F>(roots: I, mut detach: F)
+where
+    I: IntoIterator<Item = L>,
+    F: FnMut(L) -> [Option<L>; 2],
+{
+    let mut stack: Vec<_> = roots.into_iter().collect();
+
+    while let Some(link) = stack.pop() {
+        stack.extend(detach(link).into_iter().flatten());
+    }
+}
+
+/// Link `values`, in ascending order, into the right spine that inserting
+/// them one by one would build, using `link` to make a node out of a value
+/// and its right child. Tests build deep trees this way since inserting
+/// takes quadratic time on them.
+#[cfg(test)]
+pub(crate) fn right_spine<V, L, I, F>(values: I, mut link: F) -> Option<L>
+where
+    I: DoubleEndedIterator<Item = V>,
+    F: FnMut(V, Option<L>) -> L,
+{
+    values
+        .rev()
+        .fold(None, |right, value| Some(link(value, right)))
+}
@@ -0,0 +1,452 @@
+use std::{cmp::Ordering, mem};
+
+use crate::binary_search_tree::BinarySearchTree;
+
+/// An ordered map backed by a [`BinarySearchTree`] of key-value pairs.
+///
+/// Pairs are ordered by key alone, so every lookup walks the tree comparing
+/// keys, and each key is stored at most once.
+#[derive(Debug, Clone)]
+pub struct BstMap<K, V>
+where
+    K: Ord,
+{
+    tree: BinarySearchTree<Pair<K, V>>,
+}
+
+#[derive(Debug, Clone)]
+struct Pair<K, V> {
+    key: K,
+    value: V,
+}
+
+impl<K, V> PartialEq for Pair<K, V>
+where
+    K: Ord,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<K, V> Eq for Pair<K, V> where K: Ord {}
+
+impl<K, V> PartialOrd for Pair<K, V>
+where
+    K: Ord,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K, V> Ord for Pair<K, V>
+where
+    K: Ord,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+impl<K, V> Default for BstMap<K, V>
+where
+    K: Ord,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> BstMap<K, V>
+where
+    K: Ord,
+{
+    pub fn new() -> Self {
+        BstMap {
+            tree: BinarySearchTree::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Insert a key-value pair into the map. If the map already had `key`
+    /// present, its value is replaced and the old value is returned; the
+    /// stored key is left untouched.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.get_mut(&key) {
+            Some(old) => Some(mem::replace(old, value)),
+            None => {
+                self.tree.insert(Pair { key, value });
+                None
+            },
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.tree
+            .search_by(|pair| pair.key.cmp(key))
+            .map(|pair| &pair.value)
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.tree
+            .search_by_mut(|pair| pair.key.cmp(key))
+            .map(|pair| &mut pair.value)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Get the entry for `key`, for in-place insertion or modification.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        if self.contains_key(&key) {
+            let pair = self
+                .tree
+                .search_by_mut(|pair| pair.key.cmp(&key))
+                .unwrap();
+
+            Entry::Occupied(OccupiedEntry { pair })
+        } else {
+            Entry::Vacant(VacantEntry { map: self, key })
+        }
+    }
+
+    /// Remove `key` from the map, returning its value if it was present.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.remove_entry(key)
+            .map(|(_, value)| value)
+    }
+
+    pub fn remove_entry(&mut self, key: &K) -> Option<(K, V)> {
+        self.tree
+            .remove_by(|pair| pair.key.cmp(key))
+            .map(|pair| (pair.key, pair.value))
+    }
+
+    /// Return the entry with the greatest key less than or equal to `key`.
+    pub fn floor(&self, key: &K) -> Option<(&K, &V)> {
+        self.tree
+            .floor_by(|pair| pair.key.cmp(key))
+            .map(Pair::as_tuple)
+    }
+
+    /// Return the entry with the least key greater than or equal to `key`.
+    pub fn ceil(&self, key: &K) -> Option<(&K, &V)> {
+        self.tree
+            .ceil_by(|pair| pair.key.cmp(key))
+            .map(Pair::as_tuple)
+    }
+
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.tree.minimum().map(Pair::as_tuple)
+    }
+
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.tree.maximum().map(Pair::as_tuple)
+    }
+
+    /// Iterate over the entries of the map in ascending key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.tree.iter().map(Pair::as_tuple)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    /// Iterate mutably over the values of the map in ascending key order.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.tree
+            .iter_mut_unguarded()
+            .map(|pair| &mut pair.value)
+    }
+}
+
+impl<K, V> Pair<K, V> {
+    fn as_tuple(&self) -> (&K, &V) {
+        (&self.key, &self.value)
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for BstMap<K, V>
+where
+    K: Ord,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::new();
+
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+
+        map
+    }
+}
+
+/// A view into a single entry of a [`BstMap`], which may be vacant or
+/// occupied.
+pub enum Entry<'a, K, V>
+where
+    K: Ord,
+{
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+pub struct OccupiedEntry<'a, K, V> {
+    pair: &'a mut Pair<K, V>,
+}
+
+pub struct VacantEntry<'a, K, V>
+where
+    K: Ord,
+{
+    map: &'a mut BstMap<K, V>,
+    key: K,
+}
+
+impl<'a, K, V> Entry<'a, K, V>
+where
+    K: Ord,
+{
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        if let Entry::Occupied(ref mut entry) = self {
+            f(entry.get_mut());
+        }
+
+        self
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.pair.key
+    }
+
+    pub fn get(&self) -> &V {
+        &self.pair.value
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.pair.value
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.pair.value
+    }
+
+    /// Replace the value of the entry, returning the old one.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(&mut self.pair.value, value)
+    }
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V>
+where
+    K: Ord,
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Insert `value` under the entry's key and return a reference to it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let pair = self.map.tree.insert_mut(Pair {
+            key: self.key,
+            value,
+        });
+
+        &mut pair.value
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_data() -> (BstMap<usize, &'static str>, Vec<(usize, &'static str)>) {
+        let pairs = vec![
+            (5, "five"),
+            (1, "one"),
+            (4, "four"),
+            (6, "six"),
+            (9, "nine"),
+            (7, "seven"),
+        ];
+
+        (pairs.iter().copied().collect(), pairs)
+    }
+
+    #[test]
+    fn insert_and_get() {
+        let (mut map, pairs) = get_data();
+
+        assert_eq!(map.len(), pairs.len());
+
+        for (key, value) in &pairs {
+            assert_eq!(map.get(key), Some(value));
+        }
+
+        assert_eq!(map.get(&2), None);
+        assert_eq!(map.insert(4, "FOUR"), Some("four"));
+        assert_eq!(map.insert(2, "two"), None);
+        assert_eq!(map.get(&4), Some(&"FOUR"));
+        assert_eq!(map.len(), pairs.len() + 1);
+
+        *map.get_mut(&9).unwrap() = "NINE";
+        assert_eq!(map.get(&9), Some(&"NINE"));
+        assert_eq!(map.get_mut(&3), None);
+    }
+
+    #[test]
+    fn iter() {
+        let (map, mut pairs) = get_data();
+        pairs.sort();
+
+        assert_eq!(
+            map.iter()
+                .map(|(k, v)| (*k, *v))
+                .collect::<Vec<_>>(),
+            pairs
+        );
+        assert_eq!(
+            map.keys().copied().collect::<Vec<_>>(),
+            vec![1, 4, 5, 6, 7, 9]
+        );
+        assert_eq!(map.first_key_value(), Some((&1, &"one")));
+        assert_eq!(map.last_key_value(), Some((&9, &"nine")));
+    }
+
+    #[test]
+    fn values_mut() {
+        let (mut map, _) = get_data();
+
+        for value in map.values_mut() {
+            *value = &value[..1];
+        }
+
+        assert_eq!(
+            map.values()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec!["o", "f", "f", "s", "s", "n"]
+        );
+        assert_eq!(map.get(&7), Some(&"s"));
+        assert_eq!(map.floor(&8), Some((&7, &"s")));
+    }
+
+    #[test]
+    fn entry() {
+        let mut counts = BstMap::new();
+
+        for word in "a b a c b a".split(' ') {
+            *counts.entry(word).or_insert(0) += 1;
+        }
+
+        assert_eq!(
+            counts.iter().collect::<Vec<_>>(),
+            vec![(&"a", &3), (&"b", &2), (&"c", &1)]
+        );
+
+        counts
+            .entry("c")
+            .and_modify(|n| *n *= 10)
+            .or_default();
+        counts
+            .entry("d")
+            .and_modify(|n| *n *= 10)
+            .or_default();
+
+        assert_eq!(counts.get(&"c"), Some(&10));
+        assert_eq!(counts.get(&"d"), Some(&0));
+
+        match counts.entry("a") {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.key(), &"a");
+                assert_eq!(entry.insert(7), 3);
+            },
+            Entry::Vacant(_) => panic!("expected an occupied entry"),
+        }
+
+        assert_eq!(counts.get(&"a"), Some(&7));
+        assert_eq!(counts.entry("z").key(), &"z");
+        assert!(!counts.contains_key(&"z"));
+    }
+
+    #[test]
+    fn remove() {
+        let (mut map, pairs) = get_data();
+
+        assert_eq!(map.remove(&2), None);
+
+        for (key, value) in &pairs {
+            assert_eq!(map.remove(key), Some(*value));
+            assert_eq!(map.get(key), None);
+        }
+
+        assert!(map.is_empty());
+        assert_eq!(map.insert(3, "three"), None);
+        assert_eq!(map.remove_entry(&3), Some((3, "three")));
+    }
+
+    #[test]
+    fn floor_and_ceil() {
+        let (map, _) = get_data();
+
+        assert_eq!(map.floor(&0), None);
+        assert_eq!(map.floor(&3), Some((&1, &"one")));
+        assert_eq!(map.floor(&8), Some((&7, &"seven")));
+        assert_eq!(map.floor(&100), Some((&9, &"nine")));
+        assert_eq!(map.ceil(&0), Some((&1, &"one")));
+        assert_eq!(map.ceil(&5), Some((&5, &"five")));
+        assert_eq!(map.ceil(&8), Some((&9, &"nine")));
+        assert_eq!(map.ceil(&10), None);
+    }
+}
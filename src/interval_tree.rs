@@ -0,0 +1,515 @@
+use std::{cmp::Ordering, ops::Range};
+
+use crate::binary_tree::{self, BinaryNode, InOrder};
+
+/// An unbalanced binary search tree of half-open ranges, ordered by their
+/// start and then by their end.
+///
+/// Like [`BinarySearchTree`](crate::binary_search_tree::BinarySearchTree)
+/// with its default policy, the tree keeps equal ranges in nodes of their
+/// own. Every node also records the greatest end of the ranges in its
+/// subtree, so that queries can skip the subtrees that end before the
+/// point or range they look for.
+#[derive(Debug)]
+pub struct IntervalTree<T> {
+    root: Link<T>,
+    len: usize,
+}
+
+type Link<T> = Option<Box<Node<T>>>;
+
+#[derive(Debug)]
+struct Node<T> {
+    range: Range<T>,
+    max: T,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+impl<T> Node<T>
+where
+    T: Ord + Clone,
+{
+    fn new(range: Range<T>) -> Box<Self> {
+        Box::new(Node {
+            max: range.end.clone(),
+            range,
+            left: None,
+            right: None,
+        })
+    }
+
+    fn child(&mut self, side: Ordering) -> &mut Link<T> {
+        match side {
+            Ordering::Less => &mut self.left,
+            _ => &mut self.right,
+        }
+    }
+
+    fn update_max(&mut self) {
+        let max = [&self.left, &self.right]
+            .into_iter()
+            .flatten()
+            .map(|child| &child.max)
+            .fold(&self.range.end, Ord::max);
+
+        self.max = max.clone();
+    }
+}
+
+impl<T> BinaryNode for Node<T> {
+    type Value = Range<T>;
+
+    fn value(&self) -> &Range<T> {
+        &self.range
+    }
+
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+}
+
+fn compare<T>(a: &Range<T>, b: &Range<T>) -> Ordering
+where
+    T: Ord,
+{
+    (&a.start, &a.end).cmp(&(&b.start, &b.end))
+}
+
+/// Link `link` below the last node of `path` on the side it was taken
+/// from, and so on up to the first node, which is returned. The nodes are
+/// relinked bottom-up so that each records the new greatest end below it.
+fn relink<T>(path: Vec<(Box<Node<T>>, Ordering)>, link: Link<T>) -> Link<T>
+where
+    T: Ord + Clone,
+{
+    path.into_iter()
+        .rev()
+        .fold(link, |child, (mut node, side)| {
+            *node.child(side) = child;
+            node.update_max();
+
+            Some(node)
+        })
+}
+
+impl<T> Default for IntervalTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> IntervalTree<T> {
+    pub fn new() -> Self {
+        IntervalTree { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over the ranges of the tree in ascending order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter(InOrder::new(self.root.as_deref()))
+    }
+}
+
+impl<T> IntervalTree<T>
+where
+    T: Ord + Clone,
+{
+    pub fn insert(&mut self, range: Range<T>) {
+        let mut link = &mut self.root;
+
+        while let Some(node) = link {
+            if node.max < range.end {
+                node.max = range.end.clone();
+            }
+
+            link = node.child(compare(&range, &node.range));
+        }
+
+        *link = Some(Node::new(range));
+        self.len += 1;
+    }
+
+    /// Remove one occurrence of `range` from the tree and return it, or
+    /// `None` if the tree does not contain it.
+    pub fn remove(&mut self, range: &Range<T>) -> Option<Range<T>> {
+        // the nodes above the one to remove are detached on the way down,
+        // so that their greatest ends can be updated on the way back up
+        let mut path = Vec::new();
+        let mut link = self.root.take();
+
+        let removed = loop {
+            let Some(mut node) = link else {
+                self.root = relink(path, None);
+
+                return None;
+            };
+
+            let side = compare(range, &node.range);
+
+            if side == Ordering::Equal {
+                break node;
+            }
+
+            link = node.child(side).take();
+            path.push((node, side));
+        };
+
+        let Node {
+            range: removed,
+            left,
+            right,
+            ..
+        } = *removed;
+
+        let replacement = match (left, right) {
+            (None, child) | (child, None) => child,
+            (Some(left), Some(right)) => {
+                // replace the node with the least node of its right subtree
+                let mut spine = Vec::new();
+                let mut successor = right;
+
+                while let Some(left) = successor.left.take() {
+                    spine.push((successor, Ordering::Less));
+                    successor = left;
+                }
+
+                let right = successor.right.take();
+
+                successor.left = Some(left);
+                successor.right = relink(spine, right);
+                successor.update_max();
+
+                Some(successor)
+            },
+        };
+
+        self.root = relink(path, replacement);
+        self.len -= 1;
+
+        Some(removed)
+    }
+
+    /// Iterate in ascending order over the ranges that share at least one
+    /// point with `range`. An empty range overlaps nothing.
+    pub fn overlapping<'a>(&'a self, range: &'a Range<T>) -> Overlaps<'a, T> {
+        Overlaps::new(self.root.as_deref(), Query::Range(range))
+    }
+
+    /// Iterate in ascending order over the ranges that contain `point`.
+    pub fn stabbing<'a>(&'a self, point: &'a T) -> Overlaps<'a, T> {
+        Overlaps::new(self.root.as_deref(), Query::Point(point))
+    }
+}
+
+impl<T> Drop for IntervalTree<T> {
+    fn drop(&mut self) {
+        binary_tree::drop_iteratively(self.root.take(), |mut node| {
+            [node.left.take(), node.right.take()]
+        });
+    }
+}
+
+impl<T> FromIterator<Range<T>> for IntervalTree<T>
+where
+    T: Ord + Clone,
+{
+    fn from_iter<I: IntoIterator<Item = Range<T>>>(iter: I) -> Self {
+        let mut tree = Self::new();
+
+        for range in iter {
+            tree.insert(range);
+        }
+
+        tree
+    }
+}
+
+pub struct Iter<'a, T>(InOrder<'a, Node<T>>);
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a Range<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+enum Query<'a, T> {
+    Range(&'a Range<T>),
+    Point(&'a T),
+}
+
+impl<T> Query<'_, T>
+where
+    T: Ord,
+{
+    /// Return whether a range ending at `end` can match.
+    fn ends_after_start(&self, end: &T) -> bool {
+        match self {
+            Query::Range(range) => *end > range.start,
+            Query::Point(point) => end > point,
+        }
+    }
+
+    /// Return whether a range starting at `start` can match.
+    fn starts_before_end(&self, start: &T) -> bool {
+        match self {
+            Query::Range(range) => *start < range.end,
+            Query::Point(point) => start <= point,
+        }
+    }
+
+    fn matches(&self, range: &Range<T>) -> bool {
+        let within = match self {
+            Query::Range(query) => !query.is_empty(),
+            Query::Point(_) => true,
+        };
+
+        within
+            && !range.is_empty()
+            && self.starts_before_end(&range.start)
+            && self.ends_after_start(&range.end)
+    }
+}
+
+/// An iterator over the ranges of an [`IntervalTree`] that overlap a range
+/// or contain a point.
+pub struct Overlaps<'a, T> {
+    walk: InOrder<'a, Node<T>>,
+    query: Query<'a, T>,
+}
+
+impl<'a, T> Overlaps<'a, T>
+where
+    T: Ord,
+{
+    fn new(root: Option<&'a Node<T>>, query: Query<'a, T>) -> Self {
+        Overlaps {
+            walk: InOrder::pruned(root, |n| query.ends_after_start(&n.max)),
+            query,
+        }
+    }
+}
+
+impl<'a, T> Iterator for Overlaps<'a, T>
+where
+    T: Ord,
+{
+    type Item = &'a Range<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let node = self.walk.next_node()?;
+            let query = &self.query;
+
+            // the ranges to the right start no earlier than this one, and
+            // the walk skips the subtrees in which every range ends too
+            // early to match
+            if query.starts_before_end(&node.range.start) {
+                self.walk
+                    .push_left_spine(node.right.as_deref(), |n| {
+                        query.ends_after_start(&n.max)
+                    });
+            }
+
+            if query.matches(&node.range) {
+                return Some(&node.range);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_data() -> (IntervalTree<usize>, Vec<Range<usize>>) {
+        let ranges = vec![
+            15..20,
+            10..30,
+            17..19,
+            5..20,
+            12..15,
+            30..40,
+            5..20,
+            1..2,
+            25..25,
+        ];
+
+        (ranges.iter().cloned().collect(), ranges)
+    }
+
+    // checks the ordering of the tree and the greatest end of every node
+    fn check(tree: &IntervalTree<usize>) {
+        let mut stack: Vec<_> = tree
+            .root
+            .as_deref()
+            .into_iter()
+            .collect();
+
+        while let Some(node) = stack.pop() {
+            let mut max = node.range.end;
+
+            for (child, side) in [
+                (&node.left, Ordering::Greater),
+                (&node.right, Ordering::Less),
+            ] {
+                if let Some(child) = child.as_deref() {
+                    assert_ne!(compare(&child.range, &node.range), side);
+
+                    max = max.max(child.max);
+                    stack.push(child);
+                }
+            }
+
+            assert_eq!(node.max, max);
+        }
+    }
+
+    fn brute_force(
+        ranges: &[Range<usize>],
+        f: impl Fn(&Range<usize>) -> bool,
+    ) -> Vec<&Range<usize>> {
+        let mut found: Vec<_> = ranges.iter().filter(|r| f(r)).collect();
+        found.sort_by(|a, b| compare(a, b));
+
+        found
+    }
+
+    #[test]
+    fn insert() {
+        let (tree, ranges) = get_data();
+
+        check(&tree);
+
+        assert_eq!(tree.len(), ranges.len());
+        assert_eq!(
+            tree.iter().collect::<Vec<_>>(),
+            brute_force(&ranges, |_| true)
+        );
+    }
+
+    #[test]
+    fn overlapping() {
+        let (tree, ranges) = get_data();
+
+        assert_eq!(
+            tree.overlapping(&(18..26))
+                .collect::<Vec<_>>(),
+            vec![
+                &(5..20),
+                &(5..20),
+                &(10..30),
+                &(15..20),
+                &(17..19)
+            ]
+        );
+        assert_eq!(
+            tree.overlapping(&(20..21))
+                .collect::<Vec<_>>(),
+            [&(10..30)]
+        );
+        assert_eq!(tree.overlapping(&(2..5)).count(), 0);
+        assert_eq!(tree.overlapping(&(18..18)).count(), 0);
+
+        for start in 0..42 {
+            for end in start..42 {
+                let query = start..end;
+
+                assert_eq!(
+                    tree.overlapping(&query)
+                        .collect::<Vec<_>>(),
+                    brute_force(&ranges, |r| {
+                        !r.is_empty()
+                            && !query.is_empty()
+                            && r.start < query.end
+                            && query.start < r.end
+                    })
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn stabbing() {
+        let (tree, ranges) = get_data();
+
+        assert_eq!(
+            tree.stabbing(&15).collect::<Vec<_>>(),
+            vec![&(5..20), &(5..20), &(10..30), &(15..20)]
+        );
+        assert_eq!(tree.stabbing(&30).collect::<Vec<_>>(), [&(30..40)]);
+        assert_eq!(tree.stabbing(&25).collect::<Vec<_>>(), [&(10..30)]);
+        assert_eq!(tree.stabbing(&40).count(), 0);
+
+        for point in 0..42 {
+            assert_eq!(
+                tree.stabbing(&point)
+                    .collect::<Vec<_>>(),
+                brute_force(&ranges, |r| r.contains(&point))
+            );
+        }
+    }
+
+    #[test]
+    fn remove() {
+        let (mut tree, ranges) = get_data();
+
+        assert_eq!(tree.remove(&(5..21)), None);
+        assert_eq!(tree.len(), ranges.len());
+
+        for (i, range) in ranges.iter().enumerate() {
+            assert_eq!(tree.remove(range), Some(range.clone()));
+            check(&tree);
+
+            assert_eq!(
+                tree.iter().collect::<Vec<_>>(),
+                brute_force(&ranges[i + 1..], |_| true)
+            );
+            assert_eq!(
+                tree.stabbing(&18).collect::<Vec<_>>(),
+                brute_force(&ranges[i + 1..], |r| r.contains(&18))
+            );
+        }
+
+        assert!(tree.is_empty());
+        assert_eq!(tree.remove(&(1..2)), None);
+    }
+
+    #[test]
+    fn deep_tree() {
+        let len = 1_000_000;
+
+        let spine = binary_tree::right_spine(0..len, |start, right| {
+            Box::new(Node {
+                range: start..start + 2,
+                max: len + 1,
+                left: None,
+                right,
+            })
+        });
+
+        let mut tree = IntervalTree { root: spine, len };
+
+        tree.insert(len..len + 5);
+
+        assert_eq!(tree.stabbing(&(len + 4)).count(), 1);
+        assert_eq!(tree.overlapping(&(10..12)).count(), 3);
+        assert_eq!(tree.remove(&(5..7)), Some(5..7));
+        assert_eq!(tree.remove(&(len..len + 5)), Some(len..len + 5));
+        assert_eq!(tree.root.as_ref().unwrap().max, len + 1);
+        assert_eq!(tree.iter().count(), len - 1);
+    }
+}
@@ -1,8 +1,17 @@
+pub mod arena_bst;
+pub mod avl_tree;
 pub mod binary_search_tree;
+mod binary_tree;
+pub mod bst_map;
 pub mod gap_buffer;
+pub mod interval_tree;
 pub mod linked_list1;
 pub mod linked_list2;
 pub mod linked_list3;
 pub mod linked_list4;
 pub mod linked_list5;
+pub mod ordered_set;
+pub mod persistent_bst;
+pub mod splay_tree;
+pub mod treap;
 pub mod trie;
@@ -0,0 +1,457 @@
+use std::collections::{btree_set, BTreeSet};
+
+use crate::{
+    binary_search_tree::{
+        self, BinarySearchTree, Comparator, RejectDuplicates,
+    },
+    splay_tree::{self, SplayTree},
+    treap::{self, Treap},
+};
+
+/// A set of values kept in ascending order, implemented by the search trees
+/// of the crate and by `BTreeSet`, so that callers can swap one for
+/// another.
+///
+/// `contains`, `floor` and `ceil` take `&mut self`, as a [`SplayTree`]
+/// restructures itself on those lookups.
+///
+/// A [`BinarySearchTree`] only implements the trait with the
+/// [`RejectDuplicates`] policy. Under the other policies it may hold a
+/// value several times, and removing one occurrence would leave the value
+/// in the set.
+pub trait OrderedSet<T> {
+    type Iter<'a>: Iterator<Item = &'a T>
+    where
+        Self: 'a,
+        T: 'a;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Insert `value`, returning whether it was not in the set already.
+    fn insert(&mut self, value: T) -> bool;
+
+    /// Remove `value`, returning whether it was in the set.
+    fn remove(&mut self, value: &T) -> bool;
+
+    fn contains(&mut self, value: &T) -> bool;
+
+    fn min(&self) -> Option<&T>;
+
+    fn max(&self) -> Option<&T>;
+
+    /// Return the greatest value in the set less than or equal to `value`.
+    fn floor(&mut self, value: &T) -> Option<&T>;
+
+    /// Return the least value in the set greater than or equal to `value`.
+    fn ceil(&mut self, value: &T) -> Option<&T>;
+
+    /// Iterate over the values of the set in ascending order.
+    fn iter(&self) -> Self::Iter<'_>;
+}
+
+// The implementations call the inherent methods by path, since a method
+// call on `self` could resolve back to the trait method when their
+// receivers differ.
+
+impl<T, C> OrderedSet<T> for BinarySearchTree<T, RejectDuplicates, C>
+where
+    C: Comparator<T>,
+{
+    type Iter<'a>
+        = binary_search_tree::Iter<'a, T>
+    where
+        Self: 'a,
+        T: 'a;
+
+    fn len(&self) -> usize {
+        BinarySearchTree::len(self)
+    }
+
+    fn insert(&mut self, value: T) -> bool {
+        BinarySearchTree::insert(self, value)
+    }
+
+    fn remove(&mut self, value: &T) -> bool {
+        BinarySearchTree::remove(self, value).is_some()
+    }
+
+    fn contains(&mut self, value: &T) -> bool {
+        BinarySearchTree::search(self, value)
+    }
+
+    fn min(&self) -> Option<&T> {
+        BinarySearchTree::minimum(self)
+    }
+
+    fn max(&self) -> Option<&T> {
+        BinarySearchTree::maximum(self)
+    }
+
+    fn floor(&mut self, value: &T) -> Option<&T> {
+        BinarySearchTree::floor(self, value)
+    }
+
+    fn ceil(&mut self, value: &T) -> Option<&T> {
+        BinarySearchTree::ceil(self, value)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        BinarySearchTree::iter(self)
+    }
+}
+
+impl<T> OrderedSet<T> for Treap<T>
+where
+    T: Ord,
+{
+    type Iter<'a>
+        = treap::Iter<'a, T>
+    where
+        Self: 'a,
+        T: 'a;
+
+    fn len(&self) -> usize {
+        Treap::len(self)
+    }
+
+    fn insert(&mut self, value: T) -> bool {
+        Treap::insert(self, value)
+    }
+
+    fn remove(&mut self, value: &T) -> bool {
+        Treap::remove(self, value).is_some()
+    }
+
+    fn contains(&mut self, value: &T) -> bool {
+        Treap::search(self, value)
+    }
+
+    fn min(&self) -> Option<&T> {
+        Treap::minimum(self)
+    }
+
+    fn max(&self) -> Option<&T> {
+        Treap::maximum(self)
+    }
+
+    fn floor(&mut self, value: &T) -> Option<&T> {
+        Treap::floor(self, value)
+    }
+
+    fn ceil(&mut self, value: &T) -> Option<&T> {
+        Treap::ceil(self, value)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        Treap::iter(self)
+    }
+}
+
+impl<T> OrderedSet<T> for SplayTree<T>
+where
+    T: Ord,
+{
+    type Iter<'a>
+        = splay_tree::Iter<'a, T>
+    where
+        Self: 'a,
+        T: 'a;
+
+    fn len(&self) -> usize {
+        SplayTree::len(self)
+    }
+
+    fn insert(&mut self, value: T) -> bool {
+        SplayTree::insert(self, value)
+    }
+
+    fn remove(&mut self, value: &T) -> bool {
+        SplayTree::remove(self, value).is_some()
+    }
+
+    fn contains(&mut self, value: &T) -> bool {
+        SplayTree::search(self, value)
+    }
+
+    fn min(&self) -> Option<&T> {
+        SplayTree::minimum(self)
+    }
+
+    fn max(&self) -> Option<&T> {
+        SplayTree::maximum(self)
+    }
+
+    fn floor(&mut self, value: &T) -> Option<&T> {
+        SplayTree::floor(self, value)
+    }
+
+    fn ceil(&mut self, value: &T) -> Option<&T> {
+        SplayTree::ceil(self, value)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        SplayTree::iter(self)
+    }
+}
+
+impl<T> OrderedSet<T> for BTreeSet<T>
+where
+    T: Ord,
+{
+    type Iter<'a>
+        = btree_set::Iter<'a, T>
+    where
+        Self: 'a,
+        T: 'a;
+
+    fn len(&self) -> usize {
+        BTreeSet::len(self)
+    }
+
+    fn insert(&mut self, value: T) -> bool {
+        BTreeSet::insert(self, value)
+    }
+
+    fn remove(&mut self, value: &T) -> bool {
+        BTreeSet::remove(self, value)
+    }
+
+    fn contains(&mut self, value: &T) -> bool {
+        BTreeSet::contains(self, value)
+    }
+
+    fn min(&self) -> Option<&T> {
+        self.first()
+    }
+
+    fn max(&self) -> Option<&T> {
+        self.last()
+    }
+
+    fn floor(&mut self, value: &T) -> Option<&T> {
+        self.range(..=value).next_back()
+    }
+
+    fn ceil(&mut self, value: &T) -> Option<&T> {
+        self.range(value..).next()
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        BTreeSet::iter(self)
+    }
+}
+
+/// Checks that any [`OrderedSet`] can run, each taking an empty set.
+/// [`conformance_tests!`](crate::conformance_tests) turns them into tests
+/// for one implementation.
+///
+/// Outside this crate the suite is compiled with the `conformance` feature,
+/// which other crates can enable for their tests.
+#[cfg(any(test, feature = "conformance"))]
+pub mod conformance {
+    use super::OrderedSet;
+
+    // 0, 7, 14, ... taken modulo 101, which visits every value below 101
+    // in a scrambled order, and then again
+    fn values() -> impl Iterator<Item = i32> {
+        (0..202).map(|i| i * 7 % 101)
+    }
+
+    fn contents<S>(set: &S) -> Vec<i32>
+    where
+        S: OrderedSet<i32>,
+    {
+        set.iter().copied().collect()
+    }
+
+    pub fn empty<S>(mut set: S)
+    where
+        S: OrderedSet<i32>,
+    {
+        assert!(set.is_empty());
+        assert_eq!(set.len(), 0);
+        assert!(!set.contains(&0));
+        assert!(!set.remove(&0));
+        assert_eq!(set.min(), None);
+        assert_eq!(set.max(), None);
+        assert_eq!(set.floor(&0), None);
+        assert_eq!(set.ceil(&0), None);
+        assert_eq!(set.iter().next(), None);
+    }
+
+    pub fn insert<S>(mut set: S)
+    where
+        S: OrderedSet<i32>,
+    {
+        for (i, v) in values().enumerate() {
+            // the second time around, every value is already there
+            assert_eq!(set.insert(v), i < 101);
+            assert!(set.contains(&v));
+        }
+
+        assert!(!set.is_empty());
+        assert_eq!(set.len(), 101);
+        assert!(!set.contains(&-1));
+        assert!(!set.contains(&101));
+        assert_eq!(contents(&set), (0..101).collect::<Vec<_>>());
+    }
+
+    pub fn remove<S>(mut set: S)
+    where
+        S: OrderedSet<i32>,
+    {
+        values().for_each(|v| {
+            set.insert(v);
+        });
+
+        for (i, v) in values().enumerate() {
+            assert_eq!(set.remove(&v), i < 101);
+            assert!(!set.contains(&v));
+            assert_eq!(set.len(), 100usize.saturating_sub(i));
+        }
+
+        assert!(set.is_empty());
+        assert!(set.insert(5));
+        assert_eq!(contents(&set), vec![5]);
+    }
+
+    pub fn min_and_max<S>(mut set: S)
+    where
+        S: OrderedSet<i32>,
+    {
+        for v in [5, 3, 8, -2, 13] {
+            set.insert(v);
+        }
+
+        assert_eq!(set.min(), Some(&-2));
+        assert_eq!(set.max(), Some(&13));
+
+        set.remove(&-2);
+        set.remove(&13);
+
+        assert_eq!(set.min(), Some(&3));
+        assert_eq!(set.max(), Some(&8));
+
+        set.remove(&3);
+        set.remove(&8);
+
+        assert_eq!(set.min(), Some(&5));
+        assert_eq!(set.max(), Some(&5));
+    }
+
+    pub fn floor_and_ceil<S>(mut set: S)
+    where
+        S: OrderedSet<i32>,
+    {
+        let held: Vec<_> = (0..20).map(|v| v * 5).collect();
+
+        for &v in held.iter().rev() {
+            set.insert(v);
+        }
+
+        for query in -5..105 {
+            let floor = held.iter().rev().find(|v| **v <= query);
+            let ceil = held.iter().find(|v| **v >= query);
+
+            assert_eq!(set.floor(&query), floor, "floor of {query}");
+            assert_eq!(set.ceil(&query), ceil, "ceil of {query}");
+        }
+
+        // the answers hold up however the lookups rearranged the set
+        assert_eq!(contents(&set), held);
+    }
+
+    pub fn mixed_operations<S>(mut set: S)
+    where
+        S: OrderedSet<i32>,
+    {
+        let mut model = Vec::new();
+
+        for (i, v) in values().enumerate() {
+            let position = model.binary_search(&v);
+
+            if i % 3 == 2 {
+                assert_eq!(set.remove(&v), position.is_ok());
+
+                if let Ok(position) = position {
+                    model.remove(position);
+                }
+            } else {
+                assert_eq!(set.insert(v), position.is_err());
+
+                if let Err(position) = position {
+                    model.insert(position, v);
+                }
+            }
+
+            assert_eq!(set.len(), model.len());
+        }
+
+        assert_eq!(contents(&set), model);
+        assert_eq!(set.iter().count(), set.len());
+        assert_eq!(set.min(), model.first());
+        assert_eq!(set.max(), model.last());
+    }
+
+    /// Generate a test module `$name` running every check on the set that
+    /// `$new` creates. `$new` is evaluated inside the module, which imports
+    /// everything from the one the macro is called in.
+    #[macro_export]
+    macro_rules! conformance_tests {
+        ($name:ident, $new:expr) => {
+            mod $name {
+                use super::*;
+                use $crate::ordered_set::conformance;
+
+                #[test]
+                fn empty() {
+                    conformance::empty($new);
+                }
+
+                #[test]
+                fn insert() {
+                    conformance::insert($new);
+                }
+
+                #[test]
+                fn remove() {
+                    conformance::remove($new);
+                }
+
+                #[test]
+                fn min_and_max() {
+                    conformance::min_and_max($new);
+                }
+
+                #[test]
+                fn floor_and_ceil() {
+                    conformance::floor_and_ceil($new);
+                }
+
+                #[test]
+                fn mixed_operations() {
+                    conformance::mixed_operations($new);
+                }
+            }
+        };
+    }
+
+    pub use conformance_tests;
+}
+
+#[cfg(test)]
+mod test {
+    use super::{conformance::conformance_tests, *};
+
+    conformance_tests!(
+        binary_search_tree,
+        BinarySearchTree::with_policy(RejectDuplicates)
+    );
+    conformance_tests!(treap, Treap::with_seed(0));
+    conformance_tests!(splay_tree, SplayTree::new());
+    conformance_tests!(btree_set, BTreeSet::new());
+}
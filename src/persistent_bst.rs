@@ -0,0 +1,379 @@
+use std::{cmp::Ordering, rc::Rc};
+
+use crate::binary_tree::{self, BinaryNode, InOrder};
+
+/// A persistent ordered set backed by an unbalanced binary search tree.
+///
+/// `insert` and `remove` leave the tree they are called on untouched and
+/// return a new one. Only the nodes on the path to the changed value are
+/// copied, every other subtree is shared between the two versions, so
+/// keeping old versions around costs little more than the paths that
+/// changed since.
+pub struct PersistentBst<T> {
+    root: Link<T>,
+    len: usize,
+}
+
+type Link<T> = Option<Rc<Node<T>>>;
+
+struct Node<T> {
+    value: T,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+impl<T> Node<T>
+where
+    T: Clone,
+{
+    /// Copy the node, replacing its left child if `side` is `Less` or its
+    /// right child otherwise.
+    fn with_child(&self, side: Ordering, child: Link<T>) -> Rc<Self> {
+        let (left, right) = match side {
+            Ordering::Less => (child, self.right.clone()),
+            _ => (self.left.clone(), child),
+        };
+
+        Rc::new(Node {
+            value: self.value.clone(),
+            left,
+            right,
+        })
+    }
+}
+
+impl<T> BinaryNode for Node<T> {
+    type Value = T;
+
+    fn value(&self) -> &T {
+        &self.value
+    }
+
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+}
+
+/// Copy the nodes along `path` from the bottom up, linking the last one to
+/// `link` and each other one to the copy below it.
+fn copy_path<T>(path: Vec<(&Node<T>, Ordering)>, link: Link<T>) -> Link<T>
+where
+    T: Clone,
+{
+    path.into_iter()
+        .rev()
+        .fold(link, |child, (node, side)| {
+            Some(node.with_child(side, child))
+        })
+}
+
+impl<T> Clone for PersistentBst<T> {
+    fn clone(&self) -> Self {
+        PersistentBst {
+            root: self.root.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<T> Default for PersistentBst<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> PersistentBst<T> {
+    pub fn new() -> Self {
+        PersistentBst { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn minimum(&self) -> Option<&T> {
+        self.root
+            .as_deref()
+            .map(binary_tree::minimum)
+    }
+
+    pub fn maximum(&self) -> Option<&T> {
+        self.root
+            .as_deref()
+            .map(binary_tree::maximum)
+    }
+
+    /// Iterate over the values of the tree in ascending order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter(InOrder::new(self.root.as_deref()))
+    }
+}
+
+impl<T> PersistentBst<T>
+where
+    T: Ord,
+{
+    pub fn contains(&self, value: &T) -> bool {
+        let mut node = self.root.as_deref();
+
+        while let Some(n) = node {
+            node = match value.cmp(&n.value) {
+                Ordering::Equal => return true,
+                Ordering::Less => n.left.as_deref(),
+                Ordering::Greater => n.right.as_deref(),
+            };
+        }
+
+        false
+    }
+}
+
+impl<T> PersistentBst<T>
+where
+    T: Ord + Clone,
+{
+    /// Return a tree that also holds `value`, or a copy of this one if it
+    /// already does.
+    pub fn insert(&self, value: T) -> Self {
+        let mut path = Vec::new();
+        let mut link = &self.root;
+
+        while let Some(node) = link {
+            let side = value.cmp(&node.value);
+
+            link = match side {
+                Ordering::Equal => return self.clone(),
+                Ordering::Less => &node.left,
+                Ordering::Greater => &node.right,
+            };
+
+            path.push((node.as_ref(), side));
+        }
+
+        let leaf = Rc::new(Node {
+            value,
+            left: None,
+            right: None,
+        });
+
+        PersistentBst {
+            root: copy_path(path, Some(leaf)),
+            len: self.len + 1,
+        }
+    }
+
+    /// Return a tree without `value`, or a copy of this one if it does not
+    /// hold it.
+    pub fn remove(&self, value: &T) -> Self {
+        let mut path = Vec::new();
+        let mut link = &self.root;
+
+        let removed = loop {
+            let Some(node) = link else {
+                return self.clone();
+            };
+
+            let side = value.cmp(&node.value);
+
+            link = match side {
+                Ordering::Equal => break node,
+                Ordering::Less => &node.left,
+                Ordering::Greater => &node.right,
+            };
+
+            path.push((node.as_ref(), side));
+        };
+
+        let replacement = match (&removed.left, &removed.right) {
+            (None, child) | (child, None) => child.clone(),
+            (Some(_), Some(right)) => {
+                // replace the value with its successor, copying the path
+                // down the right subtree to where it was
+                let mut successor = right.as_ref();
+                let mut spine = Vec::new();
+
+                while let Some(left) = successor.left.as_deref() {
+                    spine.push((successor, Ordering::Less));
+                    successor = left;
+                }
+
+                Some(Rc::new(Node {
+                    value: successor.value.clone(),
+                    left: removed.left.clone(),
+                    right: copy_path(spine, successor.right.clone()),
+                }))
+            },
+        };
+
+        PersistentBst {
+            root: copy_path(path, replacement),
+            len: self.len - 1,
+        }
+    }
+}
+
+/// Free the nodes that no other version of the tree shares.
+impl<T> Drop for PersistentBst<T> {
+    fn drop(&mut self) {
+        binary_tree::drop_iteratively(self.root.take(), |node| {
+            match Rc::try_unwrap(node) {
+                Ok(mut node) => [node.left.take(), node.right.take()],
+                Err(_) => [None, None],
+            }
+        });
+    }
+}
+
+impl<T> FromIterator<T> for PersistentBst<T>
+where
+    T: Ord + Clone,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(Self::new(), |tree, value| tree.insert(value))
+    }
+}
+
+pub struct Iter<'a, T>(InOrder<'a, Node<T>>);
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_data() -> (PersistentBst<usize>, Vec<usize>) {
+        let vs = vec![5, 1, 4, 6, 9, 7, 2, 8];
+
+        (vs.iter().copied().collect(), vs)
+    }
+
+    fn to_vec(tree: &PersistentBst<usize>) -> Vec<usize> {
+        tree.iter().copied().collect()
+    }
+
+    #[test]
+    fn insert() {
+        let (tree, vs) = get_data();
+        let mut sorted = vs.clone();
+        sorted.sort();
+
+        assert_eq!(tree.len(), vs.len());
+        assert_eq!(to_vec(&tree), sorted);
+        assert_eq!(tree.minimum(), Some(&1));
+        assert_eq!(tree.maximum(), Some(&9));
+        assert!(vs.iter().all(|v| tree.contains(v)));
+        assert!(!tree.contains(&3));
+
+        let same = tree.insert(5);
+
+        assert_eq!(same.len(), tree.len());
+        assert!(Rc::ptr_eq(
+            same.root.as_ref().unwrap(),
+            tree.root.as_ref().unwrap()
+        ));
+
+        let more = tree.insert(3);
+
+        assert_eq!(more.len(), tree.len() + 1);
+        assert!(more.contains(&3));
+        assert!(!tree.contains(&3));
+        assert_eq!(to_vec(&tree), sorted);
+    }
+
+    #[test]
+    fn remove() {
+        let (tree, vs) = get_data();
+        let mut sorted = vs.clone();
+        sorted.sort();
+
+        assert_eq!(to_vec(&tree.remove(&3)), sorted);
+
+        let mut versions = vec![tree];
+
+        for v in &vs {
+            let next = versions.last().unwrap().remove(v);
+
+            assert!(!next.contains(v));
+            versions.push(next);
+        }
+
+        assert!(versions.last().unwrap().is_empty());
+
+        // every version still holds the values that were left in it
+        for (i, version) in versions.iter().enumerate() {
+            let mut left = vs[i..].to_vec();
+            left.sort();
+
+            assert_eq!(to_vec(version), left);
+            assert_eq!(version.len(), left.len());
+        }
+    }
+
+    #[test]
+    fn sharing() {
+        let (tree, _) = get_data();
+        let root = tree.root.as_deref().unwrap();
+
+        // 7 lies to the right of 5 and 6, so the whole left subtree of the
+        // root is shared
+        let removed = tree.remove(&7);
+        let copy = removed.root.as_deref().unwrap();
+
+        assert!(Rc::ptr_eq(
+            root.left.as_ref().unwrap(),
+            copy.left.as_ref().unwrap()
+        ));
+        assert!(!Rc::ptr_eq(
+            root.right.as_ref().unwrap(),
+            copy.right.as_ref().unwrap()
+        ));
+        assert_eq!(Rc::strong_count(root.left.as_ref().unwrap()), 2);
+
+        drop(tree);
+
+        assert_eq!(Rc::strong_count(copy.left.as_ref().unwrap()), 1);
+        assert_eq!(to_vec(&removed), vec![1, 2, 4, 5, 6, 8, 9]);
+    }
+
+    #[test]
+    fn deep_tree() {
+        let len = 100_000;
+
+        let spine = binary_tree::right_spine(0..len, |value, right| {
+            Rc::new(Node {
+                value,
+                left: None,
+                right,
+            })
+        });
+
+        let tree = PersistentBst { root: spine, len };
+        let longer = tree.insert(len);
+        let shorter = tree.remove(&(len / 2));
+
+        assert_eq!(longer.len(), len + 1);
+        assert_eq!(longer.maximum(), Some(&len));
+        assert_eq!(shorter.len(), len - 1);
+        assert!(!shorter.contains(&(len / 2)));
+        assert_eq!(tree.iter().count(), len);
+
+        drop(tree);
+
+        assert_eq!(shorter.iter().nth(len / 2), Some(&(len / 2 + 1)));
+    }
+}
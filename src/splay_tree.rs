@@ -0,0 +1,457 @@
+use std::cmp::Ordering;
+
+use crate::binary_tree::{self, BinaryNode, InOrder};
+
+/// A self-adjusting binary search tree holding each value at most once.
+///
+/// Every lookup moves the value it lands on to the root, so values that
+/// are accessed often stay near the top and the cost of a sequence of
+/// operations is logarithmic per operation amortized, even though a single
+/// one may have to walk a long path. Since lookups restructure the tree,
+/// `search`, `floor` and `ceil` take `&mut self`.
+#[derive(Debug)]
+pub struct SplayTree<T> {
+    root: Link<T>,
+    len: usize,
+}
+
+type Link<T> = Option<Box<Node<T>>>;
+
+#[derive(Debug)]
+struct Node<T> {
+    value: T,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+impl<T> Node<T> {
+    fn new(value: T) -> Box<Self> {
+        Box::new(Node {
+            value,
+            left: None,
+            right: None,
+        })
+    }
+}
+
+impl<T> BinaryNode for Node<T> {
+    type Value = T;
+
+    fn value(&self) -> &T {
+        &self.value
+    }
+
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+}
+
+/// Restructure the tree under `root` top-down so that the node holding
+/// `value`, or the last node on the path to where it would be, becomes the
+/// root.
+fn splay<T>(mut root: Box<Node<T>>, value: &T) -> Box<Node<T>>
+where
+    T: Ord,
+{
+    // the nodes found to be less than `value`, each to become the right
+    // child of the one before it, and likewise for the greater nodes on
+    // the left
+    let mut lesser = Vec::new();
+    let mut greater = Vec::new();
+
+    loop {
+        match value.cmp(&root.value) {
+            Ordering::Equal => break,
+            Ordering::Less => {
+                let Some(mut left) = root.left.take() else {
+                    break;
+                };
+
+                if *value < left.value {
+                    // rotate right, then carry on from the new root
+                    root.left = left.right.take();
+                    left.right = Some(root);
+                    root = left;
+
+                    let Some(next) = root.left.take() else {
+                        break;
+                    };
+
+                    greater.push(root);
+                    root = next;
+                } else {
+                    greater.push(root);
+                    root = left;
+                }
+            },
+            Ordering::Greater => {
+                let Some(mut right) = root.right.take() else {
+                    break;
+                };
+
+                if *value > right.value {
+                    root.right = right.left.take();
+                    right.left = Some(root);
+                    root = right;
+
+                    let Some(next) = root.right.take() else {
+                        break;
+                    };
+
+                    lesser.push(root);
+                    root = next;
+                } else {
+                    lesser.push(root);
+                    root = right;
+                }
+            },
+        }
+    }
+
+    root.left =
+        lesser
+            .into_iter()
+            .rev()
+            .fold(root.left.take(), |right, mut node| {
+                node.right = right;
+                Some(node)
+            });
+    root.right =
+        greater
+            .into_iter()
+            .rev()
+            .fold(root.right.take(), |left, mut node| {
+                node.left = left;
+                Some(node)
+            });
+
+    root
+}
+
+impl<T> Default for SplayTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> SplayTree<T> {
+    pub fn new() -> Self {
+        SplayTree { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the least value in the tree, leaving its shape as it is.
+    pub fn minimum(&self) -> Option<&T> {
+        self.root
+            .as_deref()
+            .map(binary_tree::minimum)
+    }
+
+    /// Return the greatest value in the tree, leaving its shape as it is.
+    pub fn maximum(&self) -> Option<&T> {
+        self.root
+            .as_deref()
+            .map(binary_tree::maximum)
+    }
+
+    /// Iterate over the values of the tree in ascending order, leaving its
+    /// shape as it is.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter(InOrder::new(self.root.as_deref()))
+    }
+}
+
+impl<T> SplayTree<T>
+where
+    T: Ord,
+{
+    /// Insert `value` into the tree as its new root, returning whether it
+    /// was not there already.
+    pub fn insert(&mut self, value: T) -> bool {
+        let Some(mut root) = self.root.take() else {
+            self.root = Some(Node::new(value));
+            self.len += 1;
+
+            return true;
+        };
+
+        root = splay(root, &value);
+
+        let mut node = Node::new(value);
+
+        match node.value.cmp(&root.value) {
+            Ordering::Equal => {
+                self.root = Some(root);
+
+                return false;
+            },
+            Ordering::Less => {
+                node.left = root.left.take();
+                node.right = Some(root);
+            },
+            Ordering::Greater => {
+                node.right = root.right.take();
+                node.left = Some(root);
+            },
+        }
+
+        self.root = Some(node);
+        self.len += 1;
+
+        true
+    }
+
+    /// Remove `value` from the tree and return it, or `None` if the tree
+    /// does not contain it.
+    pub fn remove(&mut self, value: &T) -> Option<T> {
+        let mut root = splay(self.root.take()?, value);
+
+        if root.value != *value {
+            self.root = Some(root);
+
+            return None;
+        }
+
+        // the greatest value to the left of the root has no right child
+        // once it is splayed, which leaves room for the right subtree
+        self.root = match root.left.take() {
+            Some(left) => {
+                let mut left = splay(left, value);
+
+                left.right = root.right.take();
+
+                Some(left)
+            },
+            None => root.right.take(),
+        };
+        self.len -= 1;
+
+        Some(root.value)
+    }
+
+    /// Return whether the tree contains `value`, moving it to the root if
+    /// it does.
+    pub fn search(&mut self, value: &T) -> bool {
+        self.splay(value)
+            .is_some_and(|root| root == value)
+    }
+
+    /// Return the greatest value in the tree less than or equal to `value`,
+    /// after moving it to the root.
+    pub fn floor(&mut self, value: &T) -> Option<&T> {
+        let mut root = self.root.take()?;
+
+        root = splay(root, value);
+
+        // the root is now next to `value`, and if it is the greater
+        // neighbour, the floor is the greatest value to its left
+        if root.value > *value {
+            if let Some(left) = root.left.take() {
+                let mut floor = splay(left, value);
+
+                root.left = floor.right.take();
+                floor.right = Some(root);
+                root = floor;
+            }
+        }
+
+        let root = self.root.insert(root);
+
+        Some(&root.value).filter(|floor| *floor <= value)
+    }
+
+    /// Return the least value in the tree greater than or equal to `value`,
+    /// after moving it to the root.
+    pub fn ceil(&mut self, value: &T) -> Option<&T> {
+        let mut root = self.root.take()?;
+
+        root = splay(root, value);
+
+        if root.value < *value {
+            if let Some(right) = root.right.take() {
+                let mut ceil = splay(right, value);
+
+                root.right = ceil.left.take();
+                ceil.left = Some(root);
+                root = ceil;
+            }
+        }
+
+        let root = self.root.insert(root);
+
+        Some(&root.value).filter(|ceil| *ceil >= value)
+    }
+
+    fn splay(&mut self, value: &T) -> Option<&T> {
+        let root = splay(self.root.take()?, value);
+
+        Some(&self.root.insert(root).value)
+    }
+}
+
+impl<T> Drop for SplayTree<T> {
+    fn drop(&mut self) {
+        binary_tree::drop_iteratively(self.root.take(), |mut node| {
+            [node.left.take(), node.right.take()]
+        });
+    }
+}
+
+impl<T> FromIterator<T> for SplayTree<T>
+where
+    T: Ord,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut tree = Self::new();
+
+        for value in iter {
+            tree.insert(value);
+        }
+
+        tree
+    }
+}
+
+pub struct Iter<'a, T>(InOrder<'a, Node<T>>);
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_data() -> (SplayTree<usize>, Vec<usize>, Vec<usize>) {
+        let vs = vec![5, 1, 4, 4, 4, 6, 5, 4, 5, 6, 5, 9, 7, 6];
+        let mut sorted = vs.clone();
+        sorted.sort();
+        sorted.dedup();
+
+        (vs.iter().copied().collect(), vs, sorted)
+    }
+
+    fn root<T>(tree: &SplayTree<T>) -> Option<&T> {
+        tree.root
+            .as_ref()
+            .map(|node| &node.value)
+    }
+
+    #[test]
+    fn insert() {
+        let (mut tree, _, sorted) = get_data();
+
+        assert_eq!(tree.len(), sorted.len());
+        assert_eq!(tree.iter().copied().collect::<Vec<_>>(), sorted);
+        assert_eq!(root(&tree), Some(&6));
+        assert!(!tree.insert(4));
+        assert_eq!(root(&tree), Some(&4));
+        assert!(tree.insert(3));
+        assert_eq!(root(&tree), Some(&3));
+        assert_eq!(tree.len(), sorted.len() + 1);
+    }
+
+    #[test]
+    fn search() {
+        let (mut tree, vs, _) = get_data();
+
+        for v in &vs {
+            assert!(tree.search(v));
+            assert_eq!(root(&tree), Some(v));
+        }
+
+        assert!(!tree.search(&3));
+        assert!(!tree.search(&100));
+        assert_eq!(root(&tree), Some(&9));
+        assert!(!SplayTree::new().search(&1));
+    }
+
+    #[test]
+    fn remove() {
+        let (mut tree, vs, mut sorted) = get_data();
+
+        assert_eq!(tree.minimum(), Some(&1));
+        assert_eq!(tree.maximum(), Some(&9));
+        assert_eq!(tree.remove(&3), None);
+        assert_eq!(tree.len(), sorted.len());
+
+        for v in &vs {
+            let i = sorted.binary_search(v);
+
+            assert_eq!(tree.remove(v), i.ok().map(|_| *v));
+
+            if let Ok(i) = i {
+                sorted.remove(i);
+            }
+
+            assert_eq!(tree.iter().copied().collect::<Vec<_>>(), sorted);
+            assert_eq!(tree.len(), sorted.len());
+        }
+
+        assert!(tree.is_empty());
+        assert_eq!(tree.maximum(), None);
+    }
+
+    #[test]
+    fn floor_and_ceil() {
+        let (mut tree, vs, sorted) = get_data();
+
+        assert_eq!(tree.floor(&0), None);
+        assert_eq!(tree.ceil(&10), None);
+
+        for query in 0..=10 {
+            let floor = sorted
+                .iter()
+                .rev()
+                .find(|v| **v <= query);
+            let ceil = sorted.iter().find(|v| **v >= query);
+
+            assert_eq!(tree.floor(&query), floor);
+
+            if floor.is_some() {
+                assert_eq!(root(&tree), floor);
+            }
+
+            assert_eq!(tree.ceil(&query), ceil);
+
+            if ceil.is_some() {
+                assert_eq!(root(&tree), ceil);
+            }
+        }
+
+        assert_eq!(tree.iter().copied().collect::<Vec<_>>(), sorted);
+        assert!(vs.iter().all(|v| tree.search(v)));
+        assert_eq!(SplayTree::<usize>::new().floor(&1), None);
+    }
+
+    #[test]
+    fn deep_tree() {
+        let len = 1_000_000;
+
+        // ascending insertions leave every older value on the left spine
+        let mut tree: SplayTree<_> = (0..len).collect();
+
+        assert!(tree.search(&0));
+        assert_eq!(root(&tree), Some(&0));
+        assert_eq!(tree.ceil(&(len / 2)), Some(&(len / 2)));
+        assert_eq!(tree.floor(&(len * 2)), Some(&(len - 1)));
+        assert!(tree.iter().copied().eq(0..len));
+        assert_eq!(tree.remove(&(len - 1)), Some(len - 1));
+        assert_eq!(tree.maximum(), Some(&(len - 2)));
+    }
+}
@@ -0,0 +1,437 @@
+use std::cmp::Ordering;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::binary_tree::{self, BinaryNode, InOrder};
+
+/// A randomized binary search tree holding each value at most once.
+///
+/// Every node draws a random priority when it is inserted, and the tree is
+/// kept a heap on those priorities by rotating new nodes up past parents of
+/// lower priority. The shape of the tree is therefore that of a tree built
+/// by inserting the values in random order, whose expected height is
+/// logarithmic whatever the order the values actually came in.
+#[derive(Debug, Clone)]
+pub struct Treap<T> {
+    root: Link<T>,
+    len: usize,
+    rng: StdRng,
+}
+
+type Link<T> = Option<Box<Node<T>>>;
+
+#[derive(Debug, Clone)]
+struct Node<T> {
+    value: T,
+    priority: u64,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+impl<T> Node<T> {
+    fn new(value: T, priority: u64) -> Box<Self> {
+        Box::new(Node {
+            value,
+            priority,
+            left: None,
+            right: None,
+        })
+    }
+}
+
+impl<T> BinaryNode for Node<T> {
+    type Value = T;
+
+    fn value(&self) -> &T {
+        &self.value
+    }
+
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+}
+
+fn rotate_left<T>(mut node: Box<Node<T>>) -> Box<Node<T>> {
+    let mut pivot = node.right.take().unwrap();
+
+    node.right = pivot.left.take();
+    pivot.left = Some(node);
+
+    pivot
+}
+
+fn rotate_right<T>(mut node: Box<Node<T>>) -> Box<Node<T>> {
+    let mut pivot = node.left.take().unwrap();
+
+    node.left = pivot.right.take();
+    pivot.right = Some(node);
+
+    pivot
+}
+
+/// Insert `value` below `link` unless it is already there, and return the
+/// new root of the subtree along with whether it grew.
+fn insert<T>(link: Link<T>, value: T, priority: u64) -> (Box<Node<T>>, bool)
+where
+    T: Ord,
+{
+    let Some(mut node) = link else {
+        return (Node::new(value, priority), true);
+    };
+
+    match value.cmp(&node.value) {
+        Ordering::Equal => (node, false),
+        Ordering::Less => {
+            let (left, inserted) = insert(node.left.take(), value, priority);
+            let rotate = left.priority > node.priority;
+
+            node.left = Some(left);
+
+            (if rotate { rotate_right(node) } else { node }, inserted)
+        },
+        Ordering::Greater => {
+            let (right, inserted) = insert(node.right.take(), value, priority);
+            let rotate = right.priority > node.priority;
+
+            node.right = Some(right);
+
+            (if rotate { rotate_left(node) } else { node }, inserted)
+        },
+    }
+}
+
+/// Join two treaps, where every value of `left` is less than every value
+/// of `right`, keeping the node of greater priority on top.
+fn merge<T>(left: Link<T>, right: Link<T>) -> Link<T> {
+    match (left, right) {
+        (None, link) | (link, None) => link,
+        (Some(mut left), Some(mut right)) => {
+            if left.priority > right.priority {
+                left.right = merge(left.right.take(), Some(right));
+
+                Some(left)
+            } else {
+                right.left = merge(Some(left), right.left.take());
+
+                Some(right)
+            }
+        },
+    }
+}
+
+/// Remove `value` from below `link` and return it, if it is there.
+fn remove<T>(link: &mut Link<T>, value: &T) -> Option<T>
+where
+    T: Ord,
+{
+    let node = link.as_mut()?;
+
+    match value.cmp(&node.value) {
+        Ordering::Less => remove(&mut node.left, value),
+        Ordering::Greater => remove(&mut node.right, value),
+        Ordering::Equal => {
+            let mut node = link.take()?;
+
+            *link = merge(node.left.take(), node.right.take());
+
+            Some(node.value)
+        },
+    }
+}
+
+impl<T> Default for Treap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Treap<T> {
+    /// Create an empty treap drawing its priorities from a generator seeded
+    /// by the operating system.
+    pub fn new() -> Self {
+        Self::with_rng(StdRng::from_entropy())
+    }
+
+    /// Create an empty treap whose shape is reproducible, given the same
+    /// `seed` and sequence of insertions.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(rng: StdRng) -> Self {
+        Treap {
+            root: None,
+            len: 0,
+            rng,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn minimum(&self) -> Option<&T> {
+        self.root
+            .as_deref()
+            .map(binary_tree::minimum)
+    }
+
+    pub fn maximum(&self) -> Option<&T> {
+        self.root
+            .as_deref()
+            .map(binary_tree::maximum)
+    }
+
+    /// Iterate over the values of the treap in ascending order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter(InOrder::new(self.root.as_deref()))
+    }
+}
+
+impl<T> Treap<T>
+where
+    T: Ord,
+{
+    /// Insert `value` into the treap, returning whether it was not there
+    /// already.
+    pub fn insert(&mut self, value: T) -> bool {
+        let priority = self.rng.gen();
+        let (root, inserted) = insert(self.root.take(), value, priority);
+
+        self.root = Some(root);
+        self.len += usize::from(inserted);
+
+        inserted
+    }
+
+    /// Remove `value` from the treap and return it, or `None` if the treap
+    /// does not contain it.
+    pub fn remove(&mut self, value: &T) -> Option<T> {
+        let removed = remove(&mut self.root, value);
+
+        self.len -= usize::from(removed.is_some());
+
+        removed
+    }
+
+    pub fn search(&self, value: &T) -> bool {
+        let mut node = self.root.as_deref();
+
+        while let Some(n) = node {
+            node = match value.cmp(&n.value) {
+                Ordering::Equal => return true,
+                Ordering::Less => n.left.as_deref(),
+                Ordering::Greater => n.right.as_deref(),
+            };
+        }
+
+        false
+    }
+
+    pub fn floor(&self, value: &T) -> Option<&T> {
+        let mut node = self.root.as_deref();
+        let mut floor = None;
+
+        while let Some(n) = node {
+            node = match n.value.cmp(value) {
+                Ordering::Equal => return Some(&n.value),
+                Ordering::Greater => n.left.as_deref(),
+                Ordering::Less => {
+                    floor = Some(&n.value);
+                    n.right.as_deref()
+                },
+            };
+        }
+
+        floor
+    }
+
+    pub fn ceil(&self, value: &T) -> Option<&T> {
+        let mut node = self.root.as_deref();
+        let mut ceil = None;
+
+        while let Some(n) = node {
+            node = match n.value.cmp(value) {
+                Ordering::Equal => return Some(&n.value),
+                Ordering::Greater => {
+                    ceil = Some(&n.value);
+                    n.left.as_deref()
+                },
+                Ordering::Less => n.right.as_deref(),
+            };
+        }
+
+        ceil
+    }
+}
+
+impl<T> FromIterator<T> for Treap<T>
+where
+    T: Ord,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut treap = Self::new();
+
+        for value in iter {
+            treap.insert(value);
+        }
+
+        treap
+    }
+}
+
+pub struct Iter<'a, T>(InOrder<'a, Node<T>>);
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_data() -> (Treap<usize>, Vec<usize>, Vec<usize>) {
+        let vs = vec![5, 1, 4, 4, 4, 6, 5, 4, 5, 6, 5, 9, 7, 6];
+        let mut sorted = vs.clone();
+        sorted.sort();
+        sorted.dedup();
+
+        (vs.iter().copied().collect(), vs, sorted)
+    }
+
+    // returns the height of the subtree, panicking if any node has a
+    // greater priority than its parent
+    fn check_heap<T>(link: &Link<T>) -> usize {
+        match link {
+            Some(node) => {
+                for child in [&node.left, &node.right]
+                    .into_iter()
+                    .flatten()
+                {
+                    assert!(child.priority <= node.priority);
+                }
+
+                1 + check_heap(&node.left).max(check_heap(&node.right))
+            },
+            None => 0,
+        }
+    }
+
+    #[test]
+    fn queries() {
+        let (treap, vs, sorted) = get_data();
+
+        check_heap(&treap.root);
+
+        assert_eq!(treap.len(), sorted.len());
+        assert_eq!(
+            treap
+                .iter()
+                .copied()
+                .collect::<Vec<_>>(),
+            sorted
+        );
+        assert!(vs.iter().all(|v| treap.search(v)));
+        assert!(!treap.search(&3));
+        assert_eq!(treap.floor(&0), None);
+        assert_eq!(treap.floor(&3), Some(&1));
+        assert_eq!(treap.floor(&8), Some(&7));
+        assert_eq!(treap.ceil(&3), Some(&4));
+        assert_eq!(treap.ceil(&9), Some(&9));
+        assert_eq!(treap.ceil(&10), None);
+    }
+
+    #[test]
+    fn insert() {
+        let mut treap = Treap::with_seed(7);
+
+        assert!(treap.insert(3));
+        assert!(treap.insert(1));
+        assert!(!treap.insert(3));
+        assert_eq!(treap.len(), 2);
+        assert!(Treap::<usize>::new().is_empty());
+    }
+
+    #[test]
+    fn remove() {
+        let (mut treap, vs, mut sorted) = get_data();
+
+        assert_eq!(treap.minimum(), Some(&1));
+        assert_eq!(treap.maximum(), Some(&9));
+        assert_eq!(treap.remove(&3), None);
+
+        for v in &vs {
+            let i = sorted.binary_search(v);
+
+            assert_eq!(treap.remove(v), i.ok().map(|_| *v));
+
+            if let Ok(i) = i {
+                sorted.remove(i);
+            }
+
+            check_heap(&treap.root);
+
+            assert_eq!(
+                treap
+                    .iter()
+                    .copied()
+                    .collect::<Vec<_>>(),
+                sorted
+            );
+            assert_eq!(treap.len(), sorted.len());
+        }
+
+        assert!(treap.is_empty());
+        assert_eq!(treap.minimum(), None);
+    }
+
+    #[test]
+    fn sorted_inserts_stay_shallow() {
+        let len = 100_000;
+        let mut treap = Treap::with_seed(0);
+
+        for v in 0..len {
+            treap.insert(v);
+        }
+
+        let height = check_heap(&treap.root);
+
+        assert_eq!(treap.len(), len);
+        assert!(treap.iter().copied().eq(0..len));
+        // the expected height is about 3 * log2(len), or 50
+        assert!(height < 80, "height {height}");
+    }
+
+    #[test]
+    fn seeded_shape() {
+        let a: Vec<_> = (0..100).collect();
+        let mut one = Treap::with_seed(42);
+        let mut other = Treap::with_seed(42);
+
+        for &v in &a {
+            one.insert(v);
+            other.insert(v);
+        }
+
+        assert_eq!(check_heap(&one.root), check_heap(&other.root));
+        assert_eq!(
+            one.root.as_ref().map(|node| node.value),
+            other
+                .root
+                .as_ref()
+                .map(|node| node.value)
+        );
+    }
+}
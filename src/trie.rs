@@ -65,10 +65,124 @@ where
 
         node.value.as_ref()
     }
+
+    /// Return whether any key stored in the trie starts with `prefix`.
+    pub fn has_prefix(&self, prefix: impl IntoIterator<Item = K>) -> bool {
+        // every node leads to a value, except for the root of an empty trie
+        self.find(prefix)
+            .is_some_and(|(_, node)| {
+                node.value.is_some() || !node.children.is_empty()
+            })
+    }
+
+    /// Iterate over the keys starting with `prefix` and their values, in no
+    /// particular order.
+    pub fn iter_prefix(
+        &self,
+        prefix: impl IntoIterator<Item = K>,
+    ) -> IterPrefix<'_, K, V>
+    where
+        K: Clone,
+    {
+        IterPrefix {
+            stack: self.find(prefix).into_iter().collect(),
+        }
+    }
+
+    /// Return up to `limit` of the keys starting with `prefix` and their
+    /// values, the values with the highest `score` first and keys of equal
+    /// score in ascending order.
+    pub fn complete<S, F>(
+        &self,
+        prefix: impl IntoIterator<Item = K>,
+        limit: usize,
+        score: F,
+    ) -> Vec<(Vec<K>, &V)>
+    where
+        K: Clone + Ord,
+        S: Ord,
+        F: Fn(&V) -> S,
+    {
+        let mut found: Vec<_> = self
+            .iter_prefix(prefix)
+            .map(|(key, value)| (score(value), key, value))
+            .collect();
+
+        let order = |a: &(S, Vec<K>, &V), b: &(S, Vec<K>, &V)| {
+            b.0.cmp(&a.0)
+                .then_with(|| a.1.cmp(&b.1))
+        };
+
+        // only the best `limit` completions need sorting
+        if limit < found.len() {
+            found.select_nth_unstable_by(limit, order);
+            found.truncate(limit);
+        }
+
+        found.sort_by(order);
+
+        found
+            .into_iter()
+            .map(|(_, key, value)| (key, value))
+            .collect()
+    }
+
+    /// Return the node reached by following `prefix`, along with the
+    /// prefix itself.
+    fn find(
+        &self,
+        prefix: impl IntoIterator<Item = K>,
+    ) -> Option<(Vec<K>, &Node<K, V>)> {
+        let mut node = &self.root;
+        let mut path = Vec::new();
+
+        for c in prefix {
+            node = node.children.get(&c)?;
+            path.push(c);
+        }
+
+        Some((path, node))
+    }
+}
+
+pub struct IterPrefix<'a, K, V>
+where
+    K: Default + Eq + Hash,
+    V: Default,
+{
+    // the nodes left to visit, with the keys that lead to them
+    stack: Vec<(Vec<K>, &'a Node<K, V>)>,
+}
+
+impl<'a, K, V> Iterator for IterPrefix<'a, K, V>
+where
+    K: Default + Eq + Hash + Clone,
+    V: Default,
+{
+    type Item = (Vec<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (key, node) = self.stack.pop()?;
+
+            for (c, child) in &node.children {
+                let mut key = key.clone();
+                key.push(c.clone());
+
+                self.stack.push((key, child));
+            }
+
+            if let Some(value) = &node.value {
+                return Some((key, value));
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use std::cmp::Reverse;
+
     use super::*;
 
     #[test]
@@ -118,4 +232,102 @@ mod test {
         // }
         println!("{}", serde_json::to_string_pretty(&trie).unwrap());
     }
+
+    fn get_data() -> Trie<char, usize> {
+        let mut trie = Trie::new();
+
+        for (word, count) in [
+            ("bar", 5),
+            ("barz", 10),
+            ("bark", 20),
+            ("bat", 20),
+            ("baton", 1),
+            ("car", 7),
+        ] {
+            trie.insert(word.chars(), count);
+        }
+
+        trie
+    }
+
+    // collects the keys back into strings, for comparing with literals
+    fn words<'a>(
+        found: impl IntoIterator<Item = (Vec<char>, &'a usize)>,
+    ) -> Vec<(String, usize)> {
+        found
+            .into_iter()
+            .map(|(key, value)| (key.into_iter().collect(), *value))
+            .collect()
+    }
+
+    fn expected(words: &[(&str, usize)]) -> Vec<(String, usize)> {
+        words
+            .iter()
+            .map(|(word, value)| (word.to_string(), *value))
+            .collect()
+    }
+
+    #[test]
+    fn iter_prefix() {
+        let trie = get_data();
+        let mut found = words(trie.iter_prefix("ba".chars()));
+        found.sort();
+
+        assert_eq!(
+            found,
+            expected(&[
+                ("bar", 5),
+                ("bark", 20),
+                ("barz", 10),
+                ("bat", 20),
+                ("baton", 1)
+            ])
+        );
+        assert_eq!(trie.iter_prefix("".chars()).count(), 6);
+        assert_eq!(
+            trie.iter_prefix("baton".chars())
+                .count(),
+            1
+        );
+        assert_eq!(
+            trie.iter_prefix("batons".chars())
+                .count(),
+            0
+        );
+        assert_eq!(trie.iter_prefix("d".chars()).count(), 0);
+    }
+
+    #[test]
+    fn has_prefix() {
+        let trie = get_data();
+
+        assert!(trie.has_prefix("".chars()));
+        assert!(trie.has_prefix("b".chars()));
+        assert!(trie.has_prefix("bato".chars()));
+        assert!(trie.has_prefix("car".chars()));
+        assert!(!trie.has_prefix("cars".chars()));
+        assert!(!trie.has_prefix("d".chars()));
+        assert!(!Trie::<char, usize>::new().has_prefix("".chars()));
+    }
+
+    #[test]
+    fn complete() {
+        let trie = get_data();
+        let complete = |prefix: &str, limit| {
+            words(trie.complete(prefix.chars(), limit, |n| *n))
+        };
+
+        // keys of equal score come in ascending order
+        assert_eq!(
+            complete("ba", 3),
+            expected(&[("bark", 20), ("bat", 20), ("barz", 10)])
+        );
+        assert_eq!(complete("ba", 100).len(), 5);
+        assert_eq!(complete("ba", 0), vec![]);
+        assert_eq!(complete("x", 3), vec![]);
+
+        let rarest = trie.complete("".chars(), 2, |n| Reverse(*n));
+
+        assert_eq!(words(rarest), expected(&[("baton", 1), ("bar", 5)]));
+    }
 }